@@ -0,0 +1,237 @@
+use gl::types::{GLenum, GLuint};
+
+use crate::gl_util;
+use crate::packer::ShelfPacker;
+
+/// Texture coordinates of a region in an atlas page
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UvRect {
+    pub u0: f32,
+    pub v0: f32,
+    pub u1: f32,
+    pub v1: f32,
+}
+
+/// The location of an image stored in the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub uv: UvRect,
+}
+
+/// The pixel format of the images stored in an atlas page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageFormat {
+    /// One coverage or distance value per pixel
+    Alpha,
+    /// Separate red, green and blue coverage values per pixel
+    Rgb,
+    /// Premultiplied red, green, blue and alpha values per pixel, for color glyphs
+    Rgba,
+}
+
+impl PageFormat {
+    /// Returns the number of bytes in each pixel
+    pub fn channels(&self) -> u32 {
+        match self {
+            PageFormat::Alpha => 1,
+            PageFormat::Rgb => 3,
+            PageFormat::Rgba => 4,
+        }
+    }
+
+    /// Returns the OpenGL internal format and pixel format of page textures
+    fn gl_formats(&self) -> (GLenum, GLenum) {
+        match self {
+            PageFormat::Alpha => (gl::R8, gl::RED),
+            PageFormat::Rgb => (gl::RGB8, gl::RGB),
+            PageFormat::Rgba => (gl::RGBA8, gl::RGBA),
+        }
+    }
+}
+
+/// A single texture in the atlas and the packer tracking its free space
+struct AtlasPage {
+    texture: GLuint,
+    format: PageFormat,
+    packer: ShelfPacker,
+}
+
+/// Stores many small images in a set of shared textures
+///
+/// Images are packed into square pages, a new page is created whenever the existing pages are full.
+/// Each page only holds images of a single pixel format.
+pub struct GlyphAtlas {
+    page_size: u32,
+    padding: u32,
+    pages: Vec<AtlasPage>,
+}
+
+impl GlyphAtlas {
+    /// Creates an empty atlas, textures are only created once images are inserted
+    /// # Arguments
+    /// * `page_size` - Width and height of each page texture in pixels
+    /// * `padding` - Empty space around each image to prevent neighbours bleeding into it
+    pub fn new(page_size: u32, padding: u32) -> GlyphAtlas {
+        GlyphAtlas {
+            page_size,
+            padding,
+            pages: vec![],
+        }
+    }
+
+    /// Returns the number of pages in the atlas
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns the texture ID of a page
+    /// # Arguments
+    /// * `page` - Index of the page
+    pub fn page_texture(&self, page: usize) -> GLuint {
+        self.pages[page].texture
+    }
+
+    /// Returns the pixel format of a page
+    /// # Arguments
+    /// * `page` - Index of the page
+    pub fn page_format(&self, page: usize) -> PageFormat {
+        self.pages[page].format
+    }
+
+    /// Copies an image into the atlas and returns where it was stored
+    /// # Arguments
+    /// * `format` - The pixel format of the image
+    /// * `width` - Width of the image in pixels
+    /// * `height` - Height of the image in pixels
+    /// * `data` - Tightly packed image data, top row first
+    pub fn insert(
+        &mut self,
+        format: PageFormat,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<AtlasRegion, String> {
+        if data.len() < (width * height * format.channels()) as usize {
+            return Err(format!(
+                "Image data of {} bytes is too small for a {}x{} image",
+                data.len(),
+                width,
+                height
+            ));
+        }
+
+        // Try to fit the image into the most recent page of the same format first
+        let mut found = None;
+        for (i, page) in self.pages.iter_mut().enumerate().rev() {
+            if page.format != format {
+                continue;
+            }
+
+            if let Some(rect) = page.packer.pack(width, height) {
+                found = Some((i, rect));
+                break;
+            }
+        }
+
+        // Otherwise create a new page for it
+        let (page, rect) = match found {
+            Some(found) => found,
+            None => {
+                let mut packer = ShelfPacker::new(self.page_size, self.page_size, self.padding);
+
+                if !packer.fits_empty(width, height) {
+                    return Err(format!(
+                        "Image of {}x{} does not fit in an atlas page of {}x{}",
+                        width, height, self.page_size, self.page_size
+                    ));
+                }
+
+                let rect = packer.pack(width, height).unwrap();
+                self.pages.push(AtlasPage {
+                    texture: create_page_texture(self.page_size, format),
+                    format,
+                    packer,
+                });
+
+                (self.pages.len() - 1, rect)
+            }
+        };
+
+        // Copy the image into the page
+        gl_util::bind_texture(self.pages[page].texture);
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                rect.x as i32,
+                rect.y as i32,
+                width as i32,
+                height as i32,
+                format.gl_formats().1,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+        gl_util::bind_texture(0);
+
+        let size = self.page_size as f32;
+        Ok(AtlasRegion {
+            page,
+            uv: UvRect {
+                u0: rect.x as f32 / size,
+                v0: rect.y as f32 / size,
+                u1: (rect.x + rect.width) as f32 / size,
+                v1: (rect.y + rect.height) as f32 / size,
+            },
+        })
+    }
+}
+
+impl Drop for GlyphAtlas {
+    /// Deletes the page textures from the GPU
+    fn drop(&mut self) {
+        for page in self.pages.iter() {
+            gl_util::delete_texture(page.texture);
+        }
+    }
+}
+
+/// Creates an empty texture for an atlas page
+/// # Arguments
+/// * `size` - Width and height of the texture
+/// * `format` - The pixel format of the texture
+fn create_page_texture(size: u32, format: PageFormat) -> GLuint {
+    let id = gl_util::generate_texture();
+    gl_util::bind_texture(id);
+
+    // Start with a cleared texture so padding around each image stays empty
+    let data = vec![0u8; (size * size * format.channels()) as usize];
+    let (internal_format, pixel_format) = format.gl_formats();
+
+    unsafe {
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            size as i32,
+            size as i32,
+            0,
+            pixel_format,
+            gl::UNSIGNED_BYTE,
+            data.as_ptr() as *const gl::types::GLvoid,
+        );
+
+        // Set texture options
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+    }
+
+    gl_util::bind_texture(0);
+
+    id
+}
@@ -0,0 +1,275 @@
+use gl::types::GLuint;
+
+use crate::atlas::{GlyphAtlas, PageFormat, UvRect};
+use crate::color::Color;
+use crate::gl_util;
+
+use std::ptr::null;
+
+/// Number of floats in a single vertex: position (x, y), texture coordinate (u, v) and
+/// color (r, g, b, a)
+pub const VERTEX_SIZE: usize = 8;
+
+/// Number of vertices used to draw a quad as two triangles
+const VERTICES_PER_QUAD: usize = 6;
+
+/// Size of the vertex buffer allocated before anything is drawn, in bytes
+const INITIAL_CAPACITY: usize = 1024 * VERTICES_PER_QUAD * VERTEX_SIZE * std::mem::size_of::<f32>();
+
+/// Accumulates textured quads and draws them with as few draw calls as possible
+///
+/// Quads are grouped by the layer they are drawn in and the atlas page they sample from. When
+/// flushed, every queued vertex is streamed to the GPU in a single upload and each page of each
+/// layer is drawn with a single draw call. Layers are drawn in order, so every quad of a layer is
+/// drawn behind every quad of the layers after it, whatever page it samples from.
+pub struct QuadBatch {
+    vao: GLuint,
+    vbo: GLuint,
+    capacity: usize,
+    /// Queued vertices by layer, then by atlas page
+    layers: Vec<Vec<Vec<f32>>>,
+    vertices: Vec<f32>,
+}
+
+impl QuadBatch {
+    /// Creates an empty batch and the GPU buffers it streams into
+    pub fn new() -> QuadBatch {
+        let vao = gl_util::generate_vertex_array();
+        let vbo = gl_util::generate_buffer();
+
+        gl_util::allocate_buffer(vbo, INITIAL_CAPACITY);
+
+        gl_util::bind_array(vao);
+        gl_util::bind_buffer(vbo);
+
+        let stride = (VERTEX_SIZE * std::mem::size_of::<f32>()) as i32;
+        unsafe {
+            // Position and texture coordinate
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, null());
+
+            // Color
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (4 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
+        }
+
+        gl_util::bind_array(0);
+        gl_util::bind_buffer(0);
+
+        QuadBatch {
+            vao,
+            vbo,
+            capacity: INITIAL_CAPACITY,
+            layers: vec![],
+            vertices: vec![],
+        }
+    }
+
+    /// Returns true if no quads are queued
+    pub fn is_empty(&self) -> bool {
+        self.layers
+            .iter()
+            .flat_map(|pages| pages.iter())
+            .all(|page| page.is_empty())
+    }
+
+    /// Queues a quad to be drawn
+    /// # Arguments
+    /// * `layer` - The layer to draw the quad in, later layers are drawn on top
+    /// * `page` - The atlas page the quad samples from
+    /// * `position` - Position of the bottom left corner
+    /// * `size` - Width and height of the quad
+    /// * `uv` - Region of the atlas page to draw
+    /// * `color` - Color of the quad
+    pub fn push_quad(
+        &mut self,
+        layer: usize,
+        page: usize,
+        position: (f32, f32),
+        size: (f32, f32),
+        uv: &UvRect,
+        color: Color,
+    ) {
+        let (x, y) = position;
+        let (w, h) = size;
+
+        // Top left, bottom left, bottom right and top right
+        self.push_corners(
+            layer,
+            page,
+            [
+                (x, y + h, uv.u0, uv.v0),
+                (x, y, uv.u0, uv.v1),
+                (x + w, y, uv.u1, uv.v1),
+                (x + w, y + h, uv.u1, uv.v0),
+            ],
+            color,
+        );
+    }
+
+    /// Queues a quad to be drawn with its region of the atlas rotated 90 degrees clockwise
+    ///
+    /// The top edge of the region is drawn down the right side of the quad, as used for glyphs
+    /// turned sideways in vertical text.
+    /// # Arguments
+    /// * `layer` - The layer to draw the quad in, later layers are drawn on top
+    /// * `page` - The atlas page the quad samples from
+    /// * `position` - Position of the bottom left corner
+    /// * `size` - Width and height of the quad, after rotation
+    /// * `uv` - Region of the atlas page to draw
+    /// * `color` - Color of the quad
+    pub fn push_rotated_quad(
+        &mut self,
+        layer: usize,
+        page: usize,
+        position: (f32, f32),
+        size: (f32, f32),
+        uv: &UvRect,
+        color: Color,
+    ) {
+        let (x, y) = position;
+        let (w, h) = size;
+
+        self.push_corners(
+            layer,
+            page,
+            [
+                (x, y + h, uv.u0, uv.v1),
+                (x, y, uv.u1, uv.v1),
+                (x + w, y, uv.u1, uv.v0),
+                (x + w, y + h, uv.u0, uv.v0),
+            ],
+            color,
+        );
+    }
+
+    /// Queues a quad as two triangles
+    /// # Arguments
+    /// * `layer` - The layer to draw the quad in
+    /// * `page` - The atlas page the quad samples from
+    /// * `corners` - Position and texture coordinate of each corner, counterclockwise from top left
+    /// * `color` - Color of the quad
+    fn push_corners(
+        &mut self,
+        layer: usize,
+        page: usize,
+        corners: [(f32, f32, f32, f32); 4],
+        color: Color,
+    ) {
+        if self.layers.len() <= layer {
+            self.layers.resize_with(layer + 1, Vec::new);
+        }
+        let pages = &mut self.layers[layer];
+        if pages.len() <= page {
+            pages.resize_with(page + 1, Vec::new);
+        }
+
+        let Color { r, g, b, a } = color;
+        for &i in [0, 1, 2, 0, 2, 3].iter() {
+            let (x, y, u, v) = corners[i];
+            pages[page].extend_from_slice(&[x, y, u, v, r, g, b, a]);
+        }
+    }
+
+    /// Discards all queued quads without drawing them
+    pub fn clear(&mut self) {
+        for page in self.layers.iter_mut().flat_map(|pages| pages.iter_mut()) {
+            page.clear();
+        }
+    }
+
+    /// Uploads all queued quads and draws them, leaving the batch empty
+    ///
+    /// The shader program used to draw the quads must already be in use.
+    /// # Arguments
+    /// * `atlas` - The atlas the queued quads sample from
+    /// * `color_glyphs` - Location of the program's `color_glyphs` uniform
+    pub fn flush(&mut self, atlas: &GlyphAtlas, color_glyphs: i32) {
+        self.upload();
+        self.draw(atlas, color_glyphs);
+        self.clear();
+    }
+
+    /// Streams all queued quads to the GPU so they can be drawn with `draw`
+    pub fn upload(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        // Gather the vertices of every page of every layer into one contiguous block
+        self.vertices.clear();
+        for page in self.layers.iter().flat_map(|pages| pages.iter()) {
+            self.vertices.extend_from_slice(page);
+        }
+
+        // Grow the buffer if needed, otherwise orphan it so the driver does not have to wait for
+        // the previous frame's draws before accepting new data
+        let size = std::mem::size_of_val(self.vertices.as_slice());
+        while self.capacity < size {
+            self.capacity *= 2;
+        }
+        gl_util::allocate_buffer(self.vbo, self.capacity);
+        gl_util::set_buffer_sub_data(self.vbo, 0, &self.vertices);
+    }
+
+    /// Draws the quads sent to the GPU by the last call to `upload`
+    ///
+    /// The quads stay queued, so they can be drawn again, for example with different blend
+    /// state. The shader program used to draw the quads must already be in use, its
+    /// `color_glyphs` uniform is set for each page so color glyphs are drawn untinted.
+    /// # Arguments
+    /// * `atlas` - The atlas the queued quads sample from
+    /// * `color_glyphs` - Location of the program's `color_glyphs` uniform
+    pub fn draw(&self, atlas: &GlyphAtlas, color_glyphs: i32) {
+        if self.is_empty() {
+            return;
+        }
+
+        // Draw each page of each layer with a single draw call
+        gl_util::bind_array(self.vao);
+
+        let mut first = 0;
+        let pages = self
+            .layers
+            .iter()
+            .flat_map(|pages| pages.iter().enumerate());
+        for (page, vertices) in pages {
+            let count = (vertices.len() / VERTEX_SIZE) as u32;
+            if count == 0 {
+                continue;
+            }
+
+            let color = atlas.page_format(page) == PageFormat::Rgba;
+            gl_util::set_uniform_int_at(color_glyphs, color as i32);
+
+            gl_util::bind_texture(atlas.page_texture(page));
+            gl_util::draw_triangles_range(first, count);
+
+            first += count;
+        }
+
+        gl_util::bind_array(0);
+        gl_util::bind_texture(0);
+    }
+}
+
+impl Default for QuadBatch {
+    fn default() -> QuadBatch {
+        QuadBatch::new()
+    }
+}
+
+impl Drop for QuadBatch {
+    /// Deletes the vertex buffers from the GPU
+    fn drop(&mut self) {
+        gl_util::delete_buffer(self.vbo);
+        gl_util::delete_vertex_array(self.vao);
+    }
+}
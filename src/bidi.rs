@@ -0,0 +1,204 @@
+use unicode_bidi::{BidiInfo, Level};
+
+use std::ops::Range;
+
+/// The direction paragraphs of text are written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Taken from the first strongly directional character of each paragraph, left to right if
+    /// there is none
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+/// The embedding levels of a paragraph resolved with the Unicode Bidirectional Algorithm (UAX #9)
+///
+/// Even levels are written left to right and odd levels right to left, so a run of Hebrew inside
+/// an English paragraph has level 1, while numbers inside that Hebrew have level 2.
+pub struct Paragraph<'a> {
+    /// Byte offset of the paragraph in the text
+    start: usize,
+    /// The requested base direction, used for paragraphs without any text
+    direction: Direction,
+    info: BidiInfo<'a>,
+}
+
+impl<'a> Paragraph<'a> {
+    /// Resolves the levels of a paragraph
+    /// # Arguments
+    /// * `text` - The text containing the paragraph
+    /// * `range` - Byte range of the paragraph, without the line break ending it
+    /// * `direction` - The base direction of the paragraph
+    pub fn new(text: &'a str, range: Range<usize>, direction: Direction) -> Paragraph<'a> {
+        let level = match direction {
+            Direction::Auto => None,
+            Direction::LeftToRight => Some(Level::ltr()),
+            Direction::RightToLeft => Some(Level::rtl()),
+        };
+
+        Paragraph {
+            start: range.start,
+            direction,
+            info: BidiInfo::new(&text[range], level),
+        }
+    }
+
+    /// Returns true if the paragraph is written right to left
+    ///
+    /// An empty paragraph has nothing to resolve, so it follows the requested direction.
+    pub fn is_rtl(&self) -> bool {
+        match self.info.paragraphs.first() {
+            Some(paragraph) => paragraph.level.is_rtl(),
+            None => self.direction == Direction::RightToLeft,
+        }
+    }
+
+    /// Returns the level of every byte of a line of the paragraph
+    ///
+    /// Whitespace at the end of the line and before tabs is given the paragraph's level, so it
+    /// stays at the end of the line in the paragraph's direction.
+    /// # Arguments
+    /// * `line` - Byte range of the line within the text
+    pub fn line_levels(&self, line: Range<usize>) -> Vec<u8> {
+        let line = line.start - self.start..line.end - self.start;
+
+        let paragraph = self
+            .info
+            .paragraphs
+            .iter()
+            .find(|paragraph| paragraph.range.contains(&line.start))
+            .or_else(|| self.info.paragraphs.last());
+        let levels = match paragraph {
+            Some(paragraph) => self.info.reordered_levels(paragraph, line.clone()),
+            None => return vec![self.is_rtl() as u8; line.len()],
+        };
+
+        levels[line].iter().map(|level| level.number()).collect()
+    }
+}
+
+/// Returns the order items are displayed in from left to right, given their levels
+///
+/// Every sequence of items at or above each level is reversed, from the highest level down to
+/// the lowest odd level. Items are indices into `levels`.
+/// # Arguments
+/// * `levels` - The level of each item in logical order
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order = (0..levels.len()).collect::<Vec<_>>();
+
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().min().unwrap_or(0) | 1;
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_paragraph_follows_direction() {
+        assert!(Paragraph::new("", 0..0, Direction::RightToLeft).is_rtl());
+        assert!(!Paragraph::new("", 0..0, Direction::LeftToRight).is_rtl());
+        assert!(!Paragraph::new("", 0..0, Direction::Auto).is_rtl());
+    }
+
+    #[test]
+    fn auto_direction_from_first_strong_character() {
+        let hebrew_first = "\u{5D0}\u{5D1} abc";
+        let latin_first = "abc \u{5D0}\u{5D1}";
+
+        assert!(Paragraph::new(hebrew_first, 0..hebrew_first.len(), Direction::Auto).is_rtl());
+        assert!(!Paragraph::new(latin_first, 0..latin_first.len(), Direction::Auto).is_rtl());
+    }
+
+    #[test]
+    fn line_levels_of_hebrew_in_english() {
+        // "ab " followed by two Hebrew letters of two bytes each and " cd"
+        let text = "ab \u{5D0}\u{5D1} cd";
+        let paragraph = Paragraph::new(text, 0..text.len(), Direction::LeftToRight);
+
+        let levels = paragraph.line_levels(0..text.len());
+
+        assert_eq!(levels, vec![0, 0, 0, 1, 1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn line_levels_of_numbers_in_hebrew() {
+        let text = "\u{5D0} 12";
+        let paragraph = Paragraph::new(text, 0..text.len(), Direction::RightToLeft);
+
+        let levels = paragraph.line_levels(0..text.len());
+
+        assert_eq!(levels, vec![1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn line_levels_of_a_later_line() {
+        // Levels are reported for the line only, and trailing whitespace takes the paragraph level
+        let text = "xx \u{5D0}\u{5D1} ";
+        let paragraph = Paragraph::new(text, 0..text.len(), Direction::RightToLeft);
+
+        let levels = paragraph.line_levels(3..text.len());
+
+        assert_eq!(levels, vec![1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn line_levels_of_paragraph_offset_in_text() {
+        let text = "first\nab \u{5D0}";
+        let paragraph = Paragraph::new(text, 6..text.len(), Direction::LeftToRight);
+
+        let levels = paragraph.line_levels(6..text.len());
+
+        assert_eq!(levels, vec![0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn empty_rtl_line_levels() {
+        let paragraph = Paragraph::new("", 0..0, Direction::RightToLeft);
+
+        assert!(paragraph.line_levels(0..0).is_empty());
+    }
+
+    #[test]
+    fn visual_order_of_single_direction() {
+        assert_eq!(visual_order(&[0, 0, 0]), vec![0, 1, 2]);
+        assert_eq!(visual_order(&[1, 1, 1]), vec![2, 1, 0]);
+        assert!(visual_order(&[]).is_empty());
+    }
+
+    #[test]
+    fn visual_order_of_rtl_run_in_ltr() {
+        assert_eq!(visual_order(&[0, 1, 1, 1, 0]), vec![0, 3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn visual_order_of_numbers_in_rtl() {
+        // The numbers keep their order while the Hebrew around them is reversed
+        assert_eq!(visual_order(&[1, 1, 2, 2, 1]), vec![4, 2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn visual_order_of_ltr_in_rtl_paragraph() {
+        assert_eq!(visual_order(&[1, 2, 2, 1, 1]), vec![4, 3, 1, 2, 0]);
+    }
+}
@@ -0,0 +1,54 @@
+/// A color with straight (not premultiplied) alpha, each component from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+
+    /// Creates an opaque color
+    /// # Arguments
+    /// * `r` - Red component
+    /// * `g` - Green component
+    /// * `b` - Blue component
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Color {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    /// Creates a color with an alpha component
+    /// # Arguments
+    /// * `r` - Red component
+    /// * `g` - Green component
+    /// * `b` - Blue component
+    /// * `a` - Alpha component, 0 is fully transparent
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Returns the color with its alpha multiplied by a factor, for fading text in and out
+    /// # Arguments
+    /// * `opacity` - Multiplier applied to the alpha component
+    pub fn faded(&self, opacity: f32) -> Color {
+        Color {
+            a: self.a * opacity,
+            ..*self
+        }
+    }
+
+    /// Returns the components as an array in red, green, blue, alpha order
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+impl Default for Color {
+    fn default() -> Color {
+        Color::WHITE
+    }
+}
@@ -0,0 +1,328 @@
+use crate::color::Color;
+
+use rustybuzz::ttf_parser;
+
+/// One layer of a layered color glyph
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    /// Index of the glyph whose outline gives the layer's shape
+    pub glyph_index: u32,
+    /// Color to fill the layer with, None for the text's foreground color
+    pub color: Option<Color>,
+}
+
+/// The layered color glyphs of a font, from its `COLR` (version 0) and `CPAL` tables
+///
+/// Each color glyph is drawn as a stack of ordinary glyphs, each filled with a solid color from
+/// the font's palette, bottom layer first.
+pub struct ColorLayers {
+    /// Glyph index, index of the first layer and number of layers, sorted by glyph index
+    base_glyphs: Vec<(u16, u16, u16)>,
+    /// Glyph index and palette entry of every layer
+    layers: Vec<(u16, u16)>,
+    /// The colors of the font's first palette
+    palette: Vec<Color>,
+}
+
+impl ColorLayers {
+    /// Reads the layered color glyphs of a face, None if the face has none
+    /// # Arguments
+    /// * `face` - The parsed face
+    pub fn parse(face: &ttf_parser::Face) -> Option<ColorLayers> {
+        let colr = face
+            .raw_face()
+            .table(ttf_parser::Tag::from_bytes(b"COLR"))?;
+        let cpal = face
+            .raw_face()
+            .table(ttf_parser::Tag::from_bytes(b"CPAL"))?;
+
+        ColorLayers::from_tables(colr, cpal)
+    }
+
+    /// Reads layered color glyphs from the data of a font's `COLR` and `CPAL` tables
+    /// # Arguments
+    /// * `colr` - The `COLR` table
+    /// * `cpal` - The `CPAL` table
+    fn from_tables(colr: &[u8], cpal: &[u8]) -> Option<ColorLayers> {
+        // Base glyph and layer records of COLR version 0
+        let base_count = read_u16(colr, 2)? as usize;
+        let base_offset = read_u32(colr, 4)? as usize;
+        let layer_offset = read_u32(colr, 8)? as usize;
+        let layer_count = read_u16(colr, 12)? as usize;
+
+        let mut base_glyphs = Vec::with_capacity(base_count);
+        for i in 0..base_count {
+            let offset = base_offset + i * 6;
+            base_glyphs.push((
+                read_u16(colr, offset)?,
+                read_u16(colr, offset + 2)?,
+                read_u16(colr, offset + 4)?,
+            ));
+        }
+        base_glyphs.sort_unstable_by_key(|base| base.0);
+
+        let mut layers = Vec::with_capacity(layer_count);
+        for i in 0..layer_count {
+            let offset = layer_offset + i * 4;
+            layers.push((read_u16(colr, offset)?, read_u16(colr, offset + 2)?));
+        }
+
+        // Colors are stored as blue, green, red, alpha bytes, the first palette is used
+        let entry_count = read_u16(cpal, 2)? as usize;
+        let records_offset = read_u32(cpal, 8)? as usize;
+        let first_record = read_u16(cpal, 12)? as usize;
+
+        let mut palette = Vec::with_capacity(entry_count);
+        for i in 0..entry_count {
+            let offset = records_offset + (first_record + i) * 4;
+            let bgra = cpal.get(offset..offset + 4)?;
+            palette.push(Color::rgba(
+                bgra[2] as f32 / 255.0,
+                bgra[1] as f32 / 255.0,
+                bgra[0] as f32 / 255.0,
+                bgra[3] as f32 / 255.0,
+            ));
+        }
+
+        if base_glyphs.is_empty() {
+            return None;
+        }
+
+        Some(ColorLayers {
+            base_glyphs,
+            layers,
+            palette,
+        })
+    }
+
+    /// Returns the layers of a glyph from bottom to top, None if it is not a layered glyph
+    /// # Arguments
+    /// * `glyph_index` - Index of the glyph in the face
+    pub fn get(&self, glyph_index: u32) -> Option<Vec<Layer>> {
+        let i = self
+            .base_glyphs
+            .binary_search_by_key(&glyph_index, |base| base.0 as u32)
+            .ok()?;
+        let (_, first, count) = self.base_glyphs[i];
+
+        let layers = self
+            .layers
+            .get(first as usize..first as usize + count as usize)?
+            .iter()
+            .map(|&(glyph_index, palette_index)| Layer {
+                glyph_index: glyph_index as u32,
+                // Palette entry 0xFFFF stands for the foreground color
+                color: self.palette.get(palette_index as usize).copied(),
+            })
+            .collect();
+
+        Some(layers)
+    }
+}
+
+/// Resizes a premultiplied RGBA image
+///
+/// Shrinking averages every source pixel under each destination pixel, enlarging interpolates
+/// between the nearest source pixels.
+/// # Arguments
+/// * `data` - Tightly packed image data, top row first
+/// * `size` - Width and height of the image
+/// * `new_size` - Width and height to resize the image to
+pub fn resample(data: &[u8], size: (usize, usize), new_size: (usize, usize)) -> Vec<u8> {
+    let (width, height) = size;
+    let (new_width, new_height) = new_size;
+    let mut resized = vec![0u8; new_width * new_height * 4];
+    if width == 0 || height == 0 {
+        return resized;
+    }
+
+    let scale_x = width as f32 / new_width as f32;
+    let scale_y = height as f32 / new_height as f32;
+    let pixel = |x: usize, y: usize, channel: usize| data[(y * width + x) * 4 + channel] as f32;
+
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let mut sum = [0.0f32; 4];
+
+            if scale_x >= 1.0 && scale_y >= 1.0 {
+                // Weigh each source pixel by how much of it lies under the destination pixel
+                let (x0, x1) = (x as f32 * scale_x, (x + 1) as f32 * scale_x);
+                let (y0, y1) = (y as f32 * scale_y, (y + 1) as f32 * scale_y);
+
+                for sy in y0 as usize..(y1.ceil() as usize).min(height) {
+                    let weight_y = (y1.min(sy as f32 + 1.0) - y0.max(sy as f32)).max(0.0);
+                    for sx in x0 as usize..(x1.ceil() as usize).min(width) {
+                        let weight =
+                            weight_y * (x1.min(sx as f32 + 1.0) - x0.max(sx as f32)).max(0.0);
+                        for (channel, total) in sum.iter_mut().enumerate() {
+                            *total += pixel(sx, sy, channel) * weight;
+                        }
+                    }
+                }
+
+                for total in sum.iter_mut() {
+                    *total /= scale_x * scale_y;
+                }
+            } else {
+                // Sample between the four source pixels around the destination pixel's center
+                let sx = ((x as f32 + 0.5) * scale_x - 0.5).clamp(0.0, (width - 1) as f32);
+                let sy = ((y as f32 + 0.5) * scale_y - 0.5).clamp(0.0, (height - 1) as f32);
+                let (left, top) = (sx as usize, sy as usize);
+                let (right, bottom) = ((left + 1).min(width - 1), (top + 1).min(height - 1));
+                let (fx, fy) = (sx - left as f32, sy - top as f32);
+
+                for (channel, total) in sum.iter_mut().enumerate() {
+                    let upper =
+                        pixel(left, top, channel) * (1.0 - fx) + pixel(right, top, channel) * fx;
+                    let lower = pixel(left, bottom, channel) * (1.0 - fx)
+                        + pixel(right, bottom, channel) * fx;
+                    *total = upper * (1.0 - fy) + lower * fy;
+                }
+            }
+
+            let i = (y * new_width + x) * 4;
+            for (channel, total) in sum.iter().enumerate() {
+                resized[i + channel] = total.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    resized
+}
+
+/// Reads a big endian 16 bit integer from a font table
+/// # Arguments
+/// * `table` - The table data
+/// * `offset` - Byte offset of the integer
+fn read_u16(table: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *table.get(offset)?,
+        *table.get(offset + 1)?,
+    ]))
+}
+
+/// Reads a big endian 32 bit integer from a font table
+/// # Arguments
+/// * `table` - The table data
+/// * `offset` - Byte offset of the integer
+fn read_u32(table: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes([
+        *table.get(offset)?,
+        *table.get(offset + 1)?,
+        *table.get(offset + 2)?,
+        *table.get(offset + 3)?,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `COLR` table with two base glyphs, listed out of order, and three layers
+    const COLR: [u8; 38] = [
+        0, 0, // version
+        0, 2, // base glyph records
+        0, 0, 0, 14, // offset of base glyph records
+        0, 0, 0, 26, // offset of layer records
+        0, 3, // layer records
+        0, 9, 0, 2, 0, 1, // glyph 9, one layer starting at layer 2
+        0, 5, 0, 0, 0, 2, // glyph 5, two layers starting at layer 0
+        0, 20, 0, 1, // glyph 20 in palette entry 1
+        0, 21, 0xFF, 0xFF, // glyph 21 in the foreground color
+        0, 22, 0, 0, // glyph 22 in palette entry 0
+    ];
+
+    /// A `CPAL` table with one palette of two colors
+    const CPAL: [u8; 22] = [
+        0, 0, // version
+        0, 2, // palette entries
+        0, 1, // palettes
+        0, 2, // color records
+        0, 0, 0, 14, // offset of color records
+        0, 0, // first color record of the palette
+        0, 0, 255, 255, // red
+        255, 0, 0, 128, // half transparent blue
+    ];
+
+    /// Returns a tightly packed image from its pixels
+    fn image(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.iter().flatten().copied().collect()
+    }
+
+    #[test]
+    fn reads_layers_bottom_first() {
+        let layers = ColorLayers::from_tables(&COLR, &CPAL).unwrap();
+
+        assert_eq!(
+            layers.get(5),
+            Some(vec![
+                Layer {
+                    glyph_index: 20,
+                    color: Some(Color::rgba(0.0, 0.0, 1.0, 128.0 / 255.0)),
+                },
+                Layer {
+                    glyph_index: 21,
+                    color: None,
+                },
+            ])
+        );
+        assert_eq!(
+            layers.get(9),
+            Some(vec![Layer {
+                glyph_index: 22,
+                color: Some(Color::rgba(1.0, 0.0, 0.0, 1.0)),
+            }])
+        );
+    }
+
+    #[test]
+    fn glyphs_without_layers_are_not_color_glyphs() {
+        let layers = ColorLayers::from_tables(&COLR, &CPAL).unwrap();
+
+        assert_eq!(layers.get(6), None);
+        assert_eq!(layers.get(20), None);
+    }
+
+    #[test]
+    fn truncated_tables_are_rejected() {
+        assert!(ColorLayers::from_tables(&COLR[..30], &CPAL).is_none());
+        assert!(ColorLayers::from_tables(&COLR, &CPAL[..18]).is_none());
+    }
+
+    #[test]
+    fn resampling_to_the_same_size_keeps_the_image() {
+        let data = image(&[
+            [10, 20, 30, 40],
+            [50, 60, 70, 80],
+            [90, 100, 110, 120],
+            [0; 4],
+        ]);
+
+        assert_eq!(resample(&data, (2, 2), (2, 2)), data);
+    }
+
+    #[test]
+    fn shrinking_averages_pixels() {
+        let data = image(&[
+            [0, 0, 0, 0],
+            [40, 80, 0, 255],
+            [80, 0, 40, 255],
+            [120, 0, 0, 255],
+        ]);
+
+        assert_eq!(resample(&data, (2, 2), (1, 1)), vec![60, 20, 10, 191]);
+    }
+
+    #[test]
+    fn enlarging_a_pixel_fills_the_image() {
+        let data = image(&[[10, 20, 30, 255]]);
+
+        assert_eq!(resample(&data, (1, 1), (2, 2)), data.repeat(4));
+    }
+
+    #[test]
+    fn resampling_an_empty_image_is_transparent() {
+        assert_eq!(resample(&[], (0, 0), (2, 2)), vec![0; 16]);
+        assert!(resample(&[], (0, 0), (0, 0)).is_empty());
+    }
+}
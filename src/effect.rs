@@ -0,0 +1,85 @@
+use crate::color::Color;
+
+/// A soft copy of the text drawn behind it, like the CSS `text-shadow`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadow {
+    /// Distance from the text to the shadow in pixels, positive values move it right and up
+    pub offset: (f32, f32),
+    /// Blur radius in pixels, the shadow has sharp edges when 0
+    pub blur: f32,
+    /// Color of the shadow, its alpha fades the shadow
+    pub color: Color,
+}
+
+impl Shadow {
+    /// Creates a shadow
+    /// # Arguments
+    /// * `offset` - Distance from the text to the shadow in pixels
+    /// * `blur` - Blur radius in pixels
+    /// * `color` - Color of the shadow
+    pub fn new(offset: (f32, f32), blur: f32, color: Color) -> Shadow {
+        Shadow {
+            offset,
+            blur,
+            color,
+        }
+    }
+}
+
+/// A halo of light around the text, added to the colors drawn behind it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glow {
+    /// How far the glow spreads from the text in pixels
+    pub radius: f32,
+    /// Color of the glow, its alpha dims the glow
+    pub color: Color,
+}
+
+impl Glow {
+    /// Creates a glow
+    /// # Arguments
+    /// * `radius` - How far the glow spreads from the text in pixels
+    /// * `color` - Color of the glow
+    pub fn new(radius: f32, color: Color) -> Glow {
+        Glow { radius, color }
+    }
+}
+
+/// How a blurred effect is combined with the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blend {
+    /// Drawn over the framebuffer, like a shadow
+    Over,
+    /// Added to the framebuffer, like a glow
+    Add,
+}
+
+/// A blurred layer of text drawn before the text itself, shared by all text with the same
+/// blur, color and blending
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Effect {
+    /// Blur radius in pixels
+    pub blur: f32,
+    pub color: Color,
+    pub blend: Blend,
+}
+
+impl From<Shadow> for Effect {
+    fn from(shadow: Shadow) -> Effect {
+        Effect {
+            blur: shadow.blur,
+            color: shadow.color,
+            blend: Blend::Over,
+        }
+    }
+}
+
+impl From<Glow> for Effect {
+    fn from(glow: Glow) -> Effect {
+        Effect {
+            blur: glow.radius,
+            color: glow.color,
+            blend: Blend::Add,
+        }
+    }
+}
@@ -0,0 +1,545 @@
+use crate::color_glyph::{ColorLayers, Layer};
+use crate::layout::FontMetrics;
+use crate::shaping::Shaper;
+use crate::variation::{self, NamedInstance, Variation, VariationAxis, VariationId};
+
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Identifies a font loaded into a renderer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FontId(pub usize);
+
+/// The contents of a font file, either embedded in the program or loaded while it runs
+///
+/// The data is kept alive for as long as any face or shaper created from it.
+#[derive(Clone, Debug)]
+pub enum FontData {
+    /// Data that lives for the whole program, such as a font included with `include_bytes!`
+    Static(&'static [u8]),
+    /// Data loaded at runtime, shared between the Freetype face and the shaper
+    Shared(Rc<Vec<u8>>),
+}
+
+impl Deref for FontData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FontData::Static(data) => data,
+            FontData::Shared(data) => data,
+        }
+    }
+}
+
+impl From<&'static [u8]> for FontData {
+    fn from(data: &'static [u8]) -> FontData {
+        FontData::Static(data)
+    }
+}
+
+impl<const N: usize> From<&'static [u8; N]> for FontData {
+    fn from(data: &'static [u8; N]) -> FontData {
+        FontData::Static(data)
+    }
+}
+
+impl From<Vec<u8>> for FontData {
+    fn from(data: Vec<u8>) -> FontData {
+        FontData::Shared(Rc::new(data))
+    }
+}
+
+impl From<Rc<Vec<u8>>> for FontData {
+    fn from(data: Rc<Vec<u8>>) -> FontData {
+        FontData::Shared(data)
+    }
+}
+
+/// A font at a particular size and variation, which glyphs are rasterized and cached for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FontInstance {
+    pub font: FontId,
+    /// The font size in points, in 26.6 fixed point
+    pub size: u32,
+    /// The axis values of a variable font, see `GlyphCache::variation`
+    pub variation: VariationId,
+}
+
+impl FontInstance {
+    /// Creates a font instance
+    /// # Arguments
+    /// * `font` - The font to use
+    /// * `size` - The font size in points
+    pub fn new(font: FontId, size: f32) -> FontInstance {
+        FontInstance {
+            font,
+            size: (size * 64.0).round().max(1.0) as u32,
+            variation: VariationId::default(),
+        }
+    }
+
+    /// Returns the font size in points
+    pub fn points(&self) -> f32 {
+        self.size as f32 / 64.0
+    }
+
+    /// Returns the horizontal and vertical size of the em square in pixels
+    /// # Arguments
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    pub fn pixels_per_em(&self, dpi: (u32, u32)) -> (f32, f32) {
+        (
+            self.points() * dpi.0 as f32 / 72.0,
+            self.points() * dpi.1 as f32 / 72.0,
+        )
+    }
+}
+
+/// The thickness of a font's strokes, from 100 (thin) to 900 (black) like CSS and OpenType
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Weight(pub u16);
+
+impl Weight {
+    pub const THIN: Weight = Weight(100);
+    pub const LIGHT: Weight = Weight(300);
+    pub const NORMAL: Weight = Weight(400);
+    pub const MEDIUM: Weight = Weight(500);
+    pub const BOLD: Weight = Weight(700);
+    pub const BLACK: Weight = Weight(900);
+}
+
+impl Default for Weight {
+    fn default() -> Weight {
+        Weight::NORMAL
+    }
+}
+
+/// Whether a font is upright or slanted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Slant {
+    #[default]
+    Normal,
+    /// A cursive design drawn specifically as the slanted companion of a family
+    Italic,
+    /// The upright design mechanically slanted
+    Oblique,
+}
+
+impl Slant {
+    /// Returns how poorly a font of this slant stands in for a requested slant, used to rank
+    /// fonts when matching a style
+    /// # Arguments
+    /// * `requested` - The requested slant
+    pub(crate) fn penalty(self, requested: Slant) -> i32 {
+        match (requested, self) {
+            (requested, candidate) if requested == candidate => 0,
+            // Italic and oblique designs can stand in for one another
+            (Slant::Italic, Slant::Oblique) | (Slant::Oblique, Slant::Italic) => 1000,
+            _ => 2000,
+        }
+    }
+}
+
+/// How condensed or expanded a font's design is, from 1 (ultra-condensed) to 9 (ultra-expanded)
+/// like the OpenType width class
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stretch(pub u16);
+
+impl Stretch {
+    pub const ULTRA_CONDENSED: Stretch = Stretch(1);
+    pub const EXTRA_CONDENSED: Stretch = Stretch(2);
+    pub const CONDENSED: Stretch = Stretch(3);
+    pub const SEMI_CONDENSED: Stretch = Stretch(4);
+    pub const NORMAL: Stretch = Stretch(5);
+    pub const SEMI_EXPANDED: Stretch = Stretch(6);
+    pub const EXPANDED: Stretch = Stretch(7);
+    pub const EXTRA_EXPANDED: Stretch = Stretch(8);
+    pub const ULTRA_EXPANDED: Stretch = Stretch(9);
+}
+
+impl Default for Stretch {
+    fn default() -> Stretch {
+        Stretch::NORMAL
+    }
+}
+
+/// The position and thickness of a line drawn through or under text, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineMetrics {
+    /// Distance from the baseline to the center of the line, negative below the baseline
+    pub position: f32,
+    pub thickness: f32,
+}
+
+/// A font face loaded from a font file
+///
+/// A single Freetype face is shared between all sizes the font is used at, it is resized before
+/// glyphs are loaded from it.
+pub struct Font {
+    face: freetype::Face,
+    shaper: Option<Shaper>,
+    family: String,
+    weight: Weight,
+    slant: Slant,
+    units_per_em: f32,
+    underline: (f32, f32),
+    strikeout: Option<(f32, f32)>,
+    axes: Vec<VariationAxis>,
+    named_instances: Vec<NamedInstance>,
+    color_layers: Option<ColorLayers>,
+    color_bitmaps: bool,
+    size: Option<u32>,
+    bitmap_scale: f32,
+    coordinates: Option<Vec<i32>>,
+}
+
+impl Font {
+    /// Loads a font face from the contents of a font file
+    /// # Arguments
+    /// * `library` - The Freetype library to load the face with
+    /// * `data` - The contents of the font file
+    /// * `face_index` - Index of the face within the font file
+    pub fn new(
+        library: &freetype::Library,
+        data: impl Into<FontData>,
+        face_index: u32,
+    ) -> Result<Font, String> {
+        let data = data.into();
+
+        let face = match &data {
+            FontData::Static(bytes) => new_static_face(library, bytes, face_index),
+            FontData::Shared(bytes) => library.new_memory_face(bytes.clone(), face_index as isize),
+        };
+        let face = match face {
+            Ok(face) => face,
+            Err(message) => return Err(format!("Unable to open font: {}", message)),
+        };
+
+        let family = face.family_name().unwrap_or_default();
+        let flags = face.style_flags();
+
+        // Prefer the weight and slant from the OS/2 table, falling back to Freetype's style flags
+        let mut weight = if flags.contains(freetype::face::StyleFlag::BOLD) {
+            Weight::BOLD
+        } else {
+            Weight::NORMAL
+        };
+        let mut slant = if flags.contains(freetype::face::StyleFlag::ITALIC) {
+            Slant::Italic
+        } else {
+            Slant::Normal
+        };
+
+        let mut strikeout = None;
+        let mut axes = vec![];
+        let mut named_instances = vec![];
+        let mut color_layers = None;
+        let mut color_bitmaps = false;
+        if let Some(parsed) = rustybuzz::Face::from_slice(&data, face_index) {
+            weight = Weight(parsed.weight().to_number());
+            if parsed.is_oblique() {
+                slant = Slant::Oblique;
+            } else if parsed.is_italic() {
+                slant = Slant::Italic;
+            }
+
+            strikeout = parsed
+                .strikeout_metrics()
+                .map(|metrics| (metrics.position as f32, metrics.thickness as f32));
+
+            let (font_axes, font_instances) = variation::read_axes(&parsed);
+            axes = font_axes;
+            named_instances = font_instances;
+
+            color_layers = ColorLayers::parse(&parsed);
+            color_bitmaps = parsed.tables().cbdt.is_some() || parsed.tables().sbix.is_some();
+        }
+
+        // Fonts without OpenType tables are laid out using Freetype's advances instead
+        let shaper = Shaper::new(data, face_index).ok();
+
+        Ok(Font {
+            units_per_em: face.em_size().max(1) as f32,
+            underline: (
+                face.underline_position() as f32,
+                face.underline_thickness() as f32,
+            ),
+            strikeout,
+            face,
+            shaper,
+            family,
+            weight,
+            slant,
+            axes,
+            named_instances,
+            color_layers,
+            color_bitmaps,
+            size: None,
+            bitmap_scale: 1.0,
+            coordinates: None,
+        })
+    }
+
+    /// Returns the Freetype face
+    pub fn face(&self) -> &freetype::Face {
+        &self.face
+    }
+
+    /// Returns the shaper for the font, None if the font can not be shaped
+    pub fn shaper(&self) -> Option<&Shaper> {
+        self.shaper.as_ref()
+    }
+
+    /// Returns the name of the font's family
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    /// Returns the weight of the font
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+
+    /// Returns the slant of the font
+    pub fn slant(&self) -> Slant {
+        self.slant
+    }
+
+    /// Returns the design axes of a variable font, empty for fonts that do not vary
+    pub fn axes(&self) -> &[VariationAxis] {
+        &self.axes
+    }
+
+    /// Returns the predefined axis values of a variable font, such as its bold instance
+    pub fn named_instances(&self) -> &[NamedInstance] {
+        &self.named_instances
+    }
+
+    /// Sets the design coordinates glyphs are loaded from the face with
+    ///
+    /// Does nothing for fonts that do not vary.
+    /// # Arguments
+    /// * `variations` - Axis values, axes without a value use their default
+    pub fn set_variation(&mut self, variations: &[Variation]) -> Result<(), String> {
+        if self.axes.is_empty() {
+            return Ok(());
+        }
+
+        let coordinates = variation::resolve(&self.axes, variations)
+            .iter()
+            .map(|axis| variation::to_fixed(axis.value))
+            .collect::<Vec<_>>();
+        if self.coordinates.as_ref() == Some(&coordinates) {
+            return Ok(());
+        }
+
+        variation::set_coordinates(&mut self.face, &coordinates)?;
+        self.coordinates = Some(coordinates);
+
+        // Metrics may vary along with the design, so the size is applied again
+        self.size = None;
+        Ok(())
+    }
+
+    /// Sets the size glyphs are loaded from the face at
+    /// # Arguments
+    /// * `size` - The font size in points, in 26.6 fixed point
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    pub fn set_size(&mut self, size: u32, dpi: (u32, u32)) -> Result<(), String> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+
+        if self.color_bitmaps && self.face.has_fixed_sizes() {
+            self.select_strike(size, dpi)?;
+        } else if let Err(message) = self.face.set_char_size(0, size as isize, dpi.0, dpi.1) {
+            return Err(format!("Unable to set font size: {}", message));
+        }
+
+        self.size = Some(size);
+        Ok(())
+    }
+
+    /// Selects the color bitmap strike glyphs are loaded from for a size
+    ///
+    /// The smallest strike at least as large as the size is used, or the largest strike if they
+    /// are all smaller, so bitmaps are usually shrunk rather than enlarged.
+    /// # Arguments
+    /// * `size` - The font size in points, in 26.6 fixed point
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    fn select_strike(&mut self, size: u32, dpi: (u32, u32)) -> Result<(), String> {
+        let pixels_per_em = size as f32 / 64.0 * dpi.1 as f32 / 72.0;
+
+        // Strike sizes are expressed in 26.6 fixed point pixels
+        let raw = self.face.raw();
+        let strikes = unsafe {
+            std::slice::from_raw_parts(raw.available_sizes, raw.num_fixed_sizes.max(0) as usize)
+        };
+        let strike_size = |i: usize| strikes[i].y_ppem as f32 / 64.0;
+
+        let larger = (0..strikes.len())
+            .filter(|&i| strike_size(i) >= pixels_per_em)
+            .min_by(|&a, &b| strike_size(a).total_cmp(&strike_size(b)));
+        let strike = match larger {
+            Some(strike) => strike,
+            None => (0..strikes.len())
+                .max_by(|&a, &b| strike_size(a).total_cmp(&strike_size(b)))
+                .ok_or_else(|| String::from("Font has no bitmap strikes"))?,
+        };
+
+        let error = unsafe { freetype::ffi::FT_Select_Size(self.face.raw_mut(), strike as i32) };
+        if error != freetype::ffi::FT_Err_Ok {
+            return Err(format!("Unable to select bitmap strike: error {}", error));
+        }
+
+        self.bitmap_scale = pixels_per_em / strike_size(strike).max(1.0);
+        Ok(())
+    }
+
+    /// Returns the layers of a layered color glyph, None if the glyph is not a color glyph
+    /// # Arguments
+    /// * `glyph_index` - Index of the glyph in the face
+    pub fn color_layers(&self, glyph_index: u32) -> Option<Vec<Layer>> {
+        self.color_layers.as_ref()?.get(glyph_index)
+    }
+
+    /// Returns true if the font draws its glyphs from color bitmaps, such as emoji fonts
+    pub fn has_color_bitmaps(&self) -> bool {
+        self.color_bitmaps
+    }
+
+    /// Returns the factor bitmaps loaded from the face are scaled by to reach the current size
+    ///
+    /// Bitmap strikes only exist at a few sizes, for other fonts this is always 1.
+    pub fn bitmap_scale(&self) -> f32 {
+        self.bitmap_scale
+    }
+
+    /// Returns the vertical metrics of the face at its current size
+    pub fn metrics(&self) -> FontMetrics {
+        match self.face.size_metrics() {
+            Some(metrics) => {
+                // Size metrics are expressed in 26.6 fixed point, at the size of the bitmap
+                // strike for bitmap fonts
+                let scale = self.bitmap_scale / 64.0;
+                let ascender = metrics.ascender as f32 * scale;
+                let descender = metrics.descender as f32 * scale;
+                let height = metrics.height as f32 * scale;
+
+                FontMetrics {
+                    ascender,
+                    descender,
+                    line_gap: (height - (ascender - descender)).max(0.0),
+                }
+            }
+            None => FontMetrics::default(),
+        }
+    }
+
+    /// Returns the position and thickness of underlines at a size
+    /// # Arguments
+    /// * `pixels_per_em` - Vertical size of the em square in pixels
+    pub fn underline(&self, pixels_per_em: f32) -> LineMetrics {
+        let scale = pixels_per_em / self.units_per_em;
+        let thickness = (self.underline.1 * scale).max(1.0);
+
+        // Fonts without underline metrics get a line just below the baseline
+        let position = if self.underline.0 != 0.0 {
+            self.underline.0 * scale
+        } else {
+            -thickness * 2.0
+        };
+
+        LineMetrics {
+            position,
+            thickness,
+        }
+    }
+
+    /// Returns the position and thickness of strikethrough lines at a size
+    /// # Arguments
+    /// * `pixels_per_em` - Vertical size of the em square in pixels
+    pub fn strikeout(&self, pixels_per_em: f32) -> LineMetrics {
+        let scale = pixels_per_em / self.units_per_em;
+
+        match self.strikeout {
+            Some((position, thickness)) if thickness > 0.0 => LineMetrics {
+                position: (position + thickness / 2.0) * scale,
+                thickness: (thickness * scale).max(1.0),
+            },
+            // Fonts without strikeout metrics get a line through the middle of lowercase letters
+            _ => LineMetrics {
+                position: pixels_per_em * 0.25,
+                thickness: self.underline(pixels_per_em).thickness,
+            },
+        }
+    }
+}
+
+/// Creates a Freetype face that reads directly from static data, without copying it
+/// # Arguments
+/// * `library` - The Freetype library to load the face with
+/// * `data` - The contents of the font file
+/// * `face_index` - Index of the face within the font file
+fn new_static_face(
+    library: &freetype::Library,
+    data: &'static [u8],
+    face_index: u32,
+) -> freetype::FtResult<freetype::Face> {
+    use freetype::ffi;
+
+    let mut face = std::ptr::null_mut();
+    let error = unsafe {
+        ffi::FT_New_Memory_Face(
+            library.raw(),
+            data.as_ptr(),
+            data.len() as ffi::FT_Long,
+            face_index as ffi::FT_Long,
+            &mut face,
+        )
+    };
+
+    if error == ffi::FT_Err_Ok {
+        // The data outlives the face, so the face does not need to hold on to it
+        Ok(unsafe { freetype::Face::from_raw(library.raw(), face, None) })
+    } else {
+        Err(error.into())
+    }
+}
+
+/// Finds the font of a family that best matches a weight and slant
+///
+/// Family names are compared without regard to case. A font with the requested slant is always
+/// preferred, then the font with the closest weight. Returns None if no font of the family is
+/// loaded.
+/// # Arguments
+/// * `fonts` - The loaded fonts
+/// * `family` - Name of the family to find
+/// * `weight` - The requested weight
+/// * `slant` - The requested slant
+pub fn find_font(fonts: &[Font], family: &str, weight: Weight, slant: Slant) -> Option<FontId> {
+    fonts
+        .iter()
+        .enumerate()
+        .filter(|(_, font)| font.family.eq_ignore_ascii_case(family))
+        .min_by_key(|(_, font)| {
+            font.slant.penalty(slant) + (font.weight.0 as i32 - weight.0 as i32).abs()
+        })
+        .map(|(i, _)| FontId(i))
+}
+
+/// Reads the contents of a font file
+/// # Arguments
+/// * `path` - Path to the font file
+pub(crate) fn read_font(path: &Path) -> Result<FontData, String> {
+    match fs::read(path) {
+        Ok(data) => Ok(FontData::from(data)),
+        Err(message) => Err(format!(
+            "Unable to read font {}: {}",
+            path.display(),
+            message
+        )),
+    }
+}
@@ -0,0 +1,580 @@
+use crate::font::{self, Slant, Stretch, Weight};
+
+use rustybuzz::ttf_parser;
+
+#[cfg(feature = "fontconfig")]
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name table IDs of the family names
+const FAMILY_NAME: u16 = 1;
+const TYPOGRAPHIC_FAMILY_NAME: u16 = 16;
+
+/// Installed families tried for generic family names when fontconfig is not available
+const GENERIC_FAMILIES: &[(&str, &[&str])] = &[
+    (
+        "serif",
+        &[
+            "DejaVu Serif",
+            "Liberation Serif",
+            "Noto Serif",
+            "Times New Roman",
+            "Times",
+        ],
+    ),
+    (
+        "sans-serif",
+        &[
+            "DejaVu Sans",
+            "Liberation Sans",
+            "Noto Sans",
+            "Arial",
+            "Helvetica",
+        ],
+    ),
+    (
+        "monospace",
+        &[
+            "DejaVu Sans Mono",
+            "Liberation Mono",
+            "Noto Sans Mono",
+            "Consolas",
+            "Menlo",
+            "Courier New",
+        ],
+    ),
+    ("cursive", &["Comic Sans MS", "Apple Chancery"]),
+    ("fantasy", &["Impact", "Papyrus"]),
+    (
+        "system-ui",
+        &[
+            "Cantarell",
+            "Ubuntu",
+            "Segoe UI",
+            "San Francisco",
+            "DejaVu Sans",
+        ],
+    ),
+];
+
+/// A font face found on disk, described by the names and style in its font tables
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaceInfo {
+    /// Path to the font file
+    pub path: PathBuf,
+    /// Index of the face within the font file, for font collections
+    pub index: u32,
+    pub family: String,
+    pub weight: Weight,
+    pub slant: Slant,
+    pub stretch: Stretch,
+}
+
+/// A request for a font face, like the CSS `font` properties
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontQuery {
+    /// Family names in order of preference, which may include generic families like `serif`
+    pub families: Vec<String>,
+    pub weight: Weight,
+    pub slant: Slant,
+    pub stretch: Stretch,
+}
+
+impl FontQuery {
+    /// Parses a comma separated query such as `"Kotta One", sans-serif, bold, italic`
+    ///
+    /// Items made up only of style keywords (weights such as `bold` or `300`, `italic`,
+    /// `oblique` and widths such as `condensed`) set the style, every other item is a family
+    /// name. Quoted items are always family names.
+    /// # Arguments
+    /// * `query` - The query to parse
+    pub fn parse(query: &str) -> FontQuery {
+        let mut parsed = FontQuery::default();
+
+        for item in query
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            // Quoted names may contain words that would otherwise be read as keywords
+            let quoted = item.len() >= 2
+                && (item.starts_with('"') && item.ends_with('"')
+                    || item.starts_with('\'') && item.ends_with('\''));
+            if quoted {
+                parsed.families.push(String::from(&item[1..item.len() - 1]));
+                continue;
+            }
+
+            let keywords = item
+                .split_whitespace()
+                .map(parse_keyword)
+                .collect::<Option<Vec<_>>>();
+            match keywords {
+                Some(keywords) => {
+                    for keyword in keywords {
+                        match keyword {
+                            Keyword::Normal => {}
+                            Keyword::Weight(weight) => parsed.weight = weight,
+                            Keyword::Slant(slant) => parsed.slant = slant,
+                            Keyword::Stretch(stretch) => parsed.stretch = stretch,
+                        }
+                    }
+                }
+                None => parsed
+                    .families
+                    .push(item.split_whitespace().collect::<Vec<_>>().join(" ")),
+            }
+        }
+
+        parsed
+    }
+}
+
+/// A style keyword in a font query
+enum Keyword {
+    Normal,
+    Weight(Weight),
+    Slant(Slant),
+    Stretch(Stretch),
+}
+
+/// Returns the style keyword a word of a query names, None if it is not a keyword
+/// # Arguments
+/// * `word` - A single word of a query
+fn parse_keyword(word: &str) -> Option<Keyword> {
+    if let Ok(weight) = word.parse::<u16>() {
+        return match weight {
+            1..=1000 => Some(Keyword::Weight(Weight(weight))),
+            _ => None,
+        };
+    }
+
+    let keyword = match word.to_ascii_lowercase().as_str() {
+        "normal" | "regular" => Keyword::Normal,
+        "thin" | "hairline" => Keyword::Weight(Weight::THIN),
+        "extra-light" | "extralight" | "ultra-light" | "ultralight" => Keyword::Weight(Weight(200)),
+        "light" => Keyword::Weight(Weight::LIGHT),
+        "medium" => Keyword::Weight(Weight::MEDIUM),
+        "semi-bold" | "semibold" | "demi-bold" | "demibold" => Keyword::Weight(Weight(600)),
+        "bold" => Keyword::Weight(Weight::BOLD),
+        "extra-bold" | "extrabold" | "ultra-bold" | "ultrabold" => Keyword::Weight(Weight(800)),
+        "black" | "heavy" => Keyword::Weight(Weight::BLACK),
+        "italic" => Keyword::Slant(Slant::Italic),
+        "oblique" => Keyword::Slant(Slant::Oblique),
+        "ultra-condensed" => Keyword::Stretch(Stretch::ULTRA_CONDENSED),
+        "extra-condensed" => Keyword::Stretch(Stretch::EXTRA_CONDENSED),
+        "condensed" => Keyword::Stretch(Stretch::CONDENSED),
+        "semi-condensed" => Keyword::Stretch(Stretch::SEMI_CONDENSED),
+        "semi-expanded" => Keyword::Stretch(Stretch::SEMI_EXPANDED),
+        "expanded" => Keyword::Stretch(Stretch::EXPANDED),
+        "extra-expanded" => Keyword::Stretch(Stretch::EXTRA_EXPANDED),
+        "ultra-expanded" => Keyword::Stretch(Stretch::ULTRA_EXPANDED),
+        _ => return None,
+    };
+
+    Some(keyword)
+}
+
+/// A collection of font faces available on the system or in chosen directories
+///
+/// Only the names and style of each face are kept, the font files are read again when a face is
+/// loaded into a renderer.
+#[derive(Default)]
+pub struct FontDatabase {
+    faces: Vec<FaceInfo>,
+    /// Path and index of every face in `faces`, to skip faces that are added again
+    loaded: HashSet<(PathBuf, u32)>,
+    #[cfg(feature = "fontconfig")]
+    config: Option<crate::fontconfig::Config>,
+}
+
+impl FontDatabase {
+    /// Creates an empty font database
+    pub fn new() -> FontDatabase {
+        FontDatabase::default()
+    }
+
+    /// Adds every font installed on the system
+    ///
+    /// Installed fonts are listed by fontconfig, which is also used to resolve generic families
+    /// afterwards. Without the `fontconfig` feature the usual font directories are scanned.
+    #[cfg(feature = "fontconfig")]
+    pub fn load_system_fonts(&mut self) -> Result<(), String> {
+        let config = crate::fontconfig::Config::new()?;
+
+        // Fontconfig lists each face of a collection separately, read each file only once
+        let mut files: Vec<(PathBuf, Vec<u32>)> = vec![];
+        let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+        for (path, index) in config.font_files() {
+            match positions.get(&path) {
+                Some(&position) => files[position].1.push(index),
+                None => {
+                    positions.insert(path.clone(), files.len());
+                    files.push((path, vec![index]));
+                }
+            }
+        }
+
+        for (path, indices) in files {
+            // Files fontconfig lists but which can not be read are left out
+            let _ = self.load_faces(&path, &indices);
+        }
+
+        self.config = Some(config);
+        Ok(())
+    }
+
+    /// Adds every font installed on the system
+    ///
+    /// Installed fonts are listed by fontconfig, which is also used to resolve generic families
+    /// afterwards. Without the `fontconfig` feature the usual font directories are scanned.
+    #[cfg(not(feature = "fontconfig"))]
+    pub fn load_system_fonts(&mut self) -> Result<(), String> {
+        let mut directories = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+            PathBuf::from("/Library/Fonts"),
+            PathBuf::from("/System/Library/Fonts"),
+            PathBuf::from("C:\\Windows\\Fonts"),
+        ];
+        if let Some(home) = std::env::var_os("HOME") {
+            directories.push(Path::new(&home).join(".local/share/fonts"));
+            directories.push(Path::new(&home).join(".fonts"));
+            directories.push(Path::new(&home).join("Library/Fonts"));
+        }
+
+        for directory in directories.iter().filter(|directory| directory.is_dir()) {
+            self.load_directory(directory)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds every font file in a directory and its subdirectories
+    ///
+    /// Files that are not fonts, or can not be parsed, are skipped.
+    /// # Arguments
+    /// * `path` - Path to the directory
+    pub fn load_directory(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let entries = match fs::read_dir(path.as_ref()) {
+            Ok(entries) => entries,
+            Err(message) => {
+                return Err(format!(
+                    "Unable to read font directory {}: {}",
+                    path.as_ref().display(),
+                    message
+                ))
+            }
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+
+            if path.is_dir() {
+                self.load_directory(&path)?;
+            } else if is_font_file(&path) {
+                let _ = self.load_file(&path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds every face of a font file
+    /// # Arguments
+    /// * `path` - Path to the font file
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let data = font::read_font(path)?;
+
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        for index in 0..count {
+            if let Some(info) = parse_face(path, &data, index) {
+                self.push(info);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns every face in the database
+    pub fn faces(&self) -> &[FaceInfo] {
+        &self.faces
+    }
+
+    /// Finds the face that best matches a query
+    ///
+    /// Families are tried in order, generic families such as `sans-serif` resolve to the family
+    /// fontconfig is configured to use. Within a family the face with the closest stretch is
+    /// preferred, then the face with the requested slant, then the face with the closest weight,
+    /// similar to CSS font matching. Returns None if no family of the query is in the database.
+    /// # Arguments
+    /// * `query` - The family names and style to find
+    pub fn query(&self, query: &FontQuery) -> Option<&FaceInfo> {
+        for family in query.families.iter() {
+            for candidate in self.resolve_family(family) {
+                if let Some(face) = self.best_face(&candidate, query) {
+                    return Some(face);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the family names to try for a family of a query
+    /// # Arguments
+    /// * `family` - A family name or generic family
+    fn resolve_family(&self, family: &str) -> Vec<String> {
+        let generic = GENERIC_FAMILIES
+            .iter()
+            .find(|(generic, _)| generic.eq_ignore_ascii_case(family));
+
+        let (_, defaults) = match generic {
+            Some(generic) => generic,
+            None => return vec![String::from(family)],
+        };
+
+        let mut families = vec![];
+
+        #[cfg(feature = "fontconfig")]
+        if let Some(config) = &self.config {
+            if let Some(matched) = config.match_family(family) {
+                families.push(matched);
+            }
+        }
+
+        families.extend(defaults.iter().map(|name| String::from(*name)));
+        families
+    }
+
+    /// Finds the face of a family that best matches the style of a query
+    /// # Arguments
+    /// * `family` - Name of the family, compared without regard to case
+    /// * `query` - The requested style
+    fn best_face(&self, family: &str, query: &FontQuery) -> Option<&FaceInfo> {
+        self.faces
+            .iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(family))
+            .min_by_key(|face| {
+                let stretch = (face.stretch.0 as i32 - query.stretch.0 as i32).abs();
+                let weight = (face.weight.0 as i32 - query.weight.0 as i32).abs();
+
+                stretch * 10000 + face.slant.penalty(query.slant) + weight
+            })
+    }
+
+    /// Adds some of the faces of a font file, reading the file once
+    ///
+    /// Faces that can not be parsed are skipped.
+    /// # Arguments
+    /// * `path` - Path to the font file
+    /// * `indices` - Indices of the faces within the file
+    #[cfg(feature = "fontconfig")]
+    fn load_faces(&mut self, path: &Path, indices: &[u32]) -> Result<(), String> {
+        let data = font::read_font(path)?;
+
+        for &index in indices {
+            if let Some(info) = parse_face(path, &data, index) {
+                self.push(info);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a face unless it is already in the database
+    /// # Arguments
+    /// * `info` - The face to add
+    fn push(&mut self, info: FaceInfo) {
+        if self.loaded.insert((info.path.clone(), info.index)) {
+            self.faces.push(info);
+        }
+    }
+}
+
+/// Returns true if a file has the extension of a font file
+/// # Arguments
+/// * `path` - Path to the file
+fn is_font_file(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => ["ttf", "otf", "ttc", "otc"]
+            .iter()
+            .any(|font| extension.eq_ignore_ascii_case(font)),
+        None => false,
+    }
+}
+
+/// Reads the family and style of a face from its font tables
+/// # Arguments
+/// * `path` - Path to the font file
+/// * `data` - The contents of the font file
+/// * `index` - Index of the face within the file
+fn parse_face(path: &Path, data: &[u8], index: u32) -> Option<FaceInfo> {
+    let face = ttf_parser::Face::parse(data, index).ok()?;
+
+    // The typographic family groups every weight and width of a family under one name, while
+    // the legacy family name is limited to four styles per family
+    let family =
+        face_name(&face, TYPOGRAPHIC_FAMILY_NAME).or_else(|| face_name(&face, FAMILY_NAME))?;
+
+    let slant = match face.style() {
+        ttf_parser::Style::Normal => Slant::Normal,
+        ttf_parser::Style::Italic => Slant::Italic,
+        ttf_parser::Style::Oblique => Slant::Oblique,
+    };
+
+    Some(FaceInfo {
+        path: path.to_path_buf(),
+        index,
+        family,
+        weight: Weight(face.weight().to_number()),
+        slant,
+        stretch: Stretch(face.width().to_number()),
+    })
+}
+
+/// Returns a name from a face's name table, preferring the US English name
+/// # Arguments
+/// * `face` - The parsed face
+/// * `name_id` - ID of the name to read
+pub(crate) fn face_name(face: &ttf_parser::Face, name_id: u16) -> Option<String> {
+    let names = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id && name.is_unicode());
+
+    let mut fallback = None;
+    for name in names {
+        // Unicode names are encoded as UTF-16 big endian
+        let units = name
+            .name
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+
+        if let Ok(value) = String::from_utf16(&units) {
+            if name.language() == ttf_parser::Language::English_UnitedStates {
+                return Some(value);
+            }
+            fallback.get_or_insert(value);
+        }
+    }
+
+    fallback
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(family: &str, index: u32, weight: u16, slant: Slant) -> FaceInfo {
+        FaceInfo {
+            path: PathBuf::from(format!("/fonts/{}.ttc", family)),
+            index,
+            family: String::from(family),
+            weight: Weight(weight),
+            slant,
+            stretch: Stretch::default(),
+        }
+    }
+
+    #[test]
+    fn parses_families_and_style() {
+        let query = FontQuery::parse("\"Kotta One\", sans-serif, bold, italic");
+
+        assert_eq!(query.families, vec!["Kotta One", "sans-serif"]);
+        assert_eq!(query.weight, Weight::BOLD);
+        assert_eq!(query.slant, Slant::Italic);
+        assert_eq!(query.stretch, Stretch::default());
+    }
+
+    #[test]
+    fn parses_combined_keywords() {
+        let query = FontQuery::parse("Noto Sans, 300 condensed oblique");
+
+        assert_eq!(query.families, vec!["Noto Sans"]);
+        assert_eq!(query.weight, Weight(300));
+        assert_eq!(query.slant, Slant::Oblique);
+        assert_eq!(query.stretch, Stretch::CONDENSED);
+    }
+
+    #[test]
+    fn keywords_are_case_insensitive() {
+        let query = FontQuery::parse("serif, Semi-Bold Italic");
+
+        assert_eq!(query.weight, Weight(600));
+        assert_eq!(query.slant, Slant::Italic);
+    }
+
+    #[test]
+    fn quoted_keywords_are_families() {
+        let query = FontQuery::parse("'Bold', \"Black\"");
+
+        assert_eq!(query.families, vec!["Bold", "Black"]);
+        assert_eq!(query.weight, Weight::default());
+    }
+
+    #[test]
+    fn family_names_containing_keywords() {
+        // Not every word is a keyword, so the whole item is a family name
+        let query = FontQuery::parse("Roboto   Light, Open Sans Bold Italic");
+
+        assert_eq!(
+            query.families,
+            vec!["Roboto Light", "Open Sans Bold Italic"]
+        );
+        assert_eq!(query.weight, Weight::default());
+        assert_eq!(query.slant, Slant::Normal);
+    }
+
+    #[test]
+    fn invalid_weights_are_family_names() {
+        let query = FontQuery::parse("0, 1001, normal");
+
+        assert_eq!(query.families, vec!["0", "1001"]);
+        assert_eq!(query.weight, Weight::default());
+    }
+
+    #[test]
+    fn empty_items_are_skipped() {
+        assert_eq!(FontQuery::parse(""), FontQuery::default());
+        assert_eq!(FontQuery::parse(" , ,"), FontQuery::default());
+    }
+
+    #[test]
+    fn duplicate_faces_are_skipped() {
+        let mut database = FontDatabase::new();
+        database.push(face("Serif", 0, 400, Slant::Normal));
+        database.push(face("Serif", 1, 700, Slant::Normal));
+        database.push(face("Serif", 0, 400, Slant::Normal));
+
+        assert_eq!(database.faces().len(), 2);
+    }
+
+    #[test]
+    fn query_prefers_slant_then_weight() {
+        let mut database = FontDatabase::new();
+        database.push(face("Kotta", 0, 400, Slant::Normal));
+        database.push(face("Kotta", 1, 700, Slant::Normal));
+        database.push(face("Kotta", 2, 400, Slant::Oblique));
+
+        let bold = database.query(&FontQuery::parse("Missing, kotta, bold"));
+        assert_eq!(bold.map(|face| face.index), Some(1));
+
+        // Oblique stands in for italic even though the bold face is closer in weight
+        let italic = database.query(&FontQuery::parse("Kotta, bold italic"));
+        assert_eq!(italic.map(|face| face.index), Some(2));
+
+        assert!(database.query(&FontQuery::parse("Missing")).is_none());
+    }
+
+    #[test]
+    fn slant_penalty_ranks_substitutes() {
+        assert_eq!(Slant::Italic.penalty(Slant::Italic), 0);
+        assert!(Slant::Oblique.penalty(Slant::Italic) < Slant::Normal.penalty(Slant::Italic));
+        assert!(Slant::Italic.penalty(Slant::Normal) > Slant::Normal.penalty(Slant::Normal));
+    }
+}
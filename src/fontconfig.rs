@@ -0,0 +1,186 @@
+//! Minimal bindings to the parts of fontconfig used to find installed fonts
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uchar};
+use std::path::PathBuf;
+
+#[repr(C)]
+struct FcConfig {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct FcPattern {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct FcObjectSet {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct FcFontSet {
+    nfont: c_int,
+    sfont: c_int,
+    fonts: *mut *mut FcPattern,
+}
+
+/// `FcResultMatch`, returned when a value was found
+const FC_RESULT_MATCH: c_int = 0;
+
+/// `FcMatchPattern`, applies substitutions meant for font queries
+const FC_MATCH_PATTERN: c_int = 0;
+
+#[link(name = "fontconfig")]
+extern "C" {
+    fn FcInitLoadConfigAndFonts() -> *mut FcConfig;
+    fn FcConfigDestroy(config: *mut FcConfig);
+    fn FcPatternCreate() -> *mut FcPattern;
+    fn FcPatternDestroy(pattern: *mut FcPattern);
+    fn FcPatternGetString(
+        pattern: *const FcPattern,
+        object: *const c_char,
+        n: c_int,
+        value: *mut *mut c_uchar,
+    ) -> c_int;
+    fn FcPatternGetInteger(
+        pattern: *const FcPattern,
+        object: *const c_char,
+        n: c_int,
+        value: *mut c_int,
+    ) -> c_int;
+    fn FcObjectSetCreate() -> *mut FcObjectSet;
+    fn FcObjectSetAdd(set: *mut FcObjectSet, object: *const c_char) -> c_int;
+    fn FcObjectSetDestroy(set: *mut FcObjectSet);
+    fn FcFontList(
+        config: *mut FcConfig,
+        pattern: *mut FcPattern,
+        objects: *mut FcObjectSet,
+    ) -> *mut FcFontSet;
+    fn FcFontSetDestroy(set: *mut FcFontSet);
+    fn FcNameParse(name: *const c_uchar) -> *mut FcPattern;
+    fn FcConfigSubstitute(config: *mut FcConfig, pattern: *mut FcPattern, kind: c_int) -> c_int;
+    fn FcDefaultSubstitute(pattern: *mut FcPattern);
+    fn FcFontMatch(
+        config: *mut FcConfig,
+        pattern: *mut FcPattern,
+        result: *mut c_int,
+    ) -> *mut FcPattern;
+}
+
+/// A loaded fontconfig configuration
+pub struct Config {
+    raw: *mut FcConfig,
+}
+
+impl Config {
+    /// Loads the system's fontconfig configuration and font list
+    pub fn new() -> Result<Config, String> {
+        let raw = unsafe { FcInitLoadConfigAndFonts() };
+
+        if raw.is_null() {
+            Err(String::from("Unable to load the fontconfig configuration"))
+        } else {
+            Ok(Config { raw })
+        }
+    }
+
+    /// Returns the file path and face index of every installed font
+    pub fn font_files(&self) -> Vec<(PathBuf, u32)> {
+        let file = CString::new("file").unwrap();
+        let index = CString::new("index").unwrap();
+        let mut files = vec![];
+
+        unsafe {
+            let pattern = FcPatternCreate();
+            let objects = FcObjectSetCreate();
+            FcObjectSetAdd(objects, file.as_ptr());
+            FcObjectSetAdd(objects, index.as_ptr());
+
+            let set = FcFontList(self.raw, pattern, objects);
+            if !set.is_null() {
+                for i in 0..(*set).nfont as usize {
+                    let font = *(*set).fonts.add(i);
+
+                    let path = match get_string(font, &file) {
+                        Some(path) => PathBuf::from(path),
+                        None => continue,
+                    };
+
+                    let mut face_index: c_int = 0;
+                    FcPatternGetInteger(font, index.as_ptr(), 0, &mut face_index);
+
+                    files.push((path, face_index.max(0) as u32));
+                }
+                FcFontSetDestroy(set);
+            }
+
+            FcObjectSetDestroy(objects);
+            FcPatternDestroy(pattern);
+        }
+
+        files
+    }
+
+    /// Returns the installed family fontconfig would use for a family name, such as the
+    /// configured default for a generic family like `sans-serif`
+    /// # Arguments
+    /// * `family` - The family name to look up
+    pub fn match_family(&self, family: &str) -> Option<String> {
+        let name = CString::new(family).ok()?;
+        let object = CString::new("family").unwrap();
+
+        unsafe {
+            let pattern = FcNameParse(name.as_ptr() as *const c_uchar);
+            if pattern.is_null() {
+                return None;
+            }
+
+            FcConfigSubstitute(self.raw, pattern, FC_MATCH_PATTERN);
+            FcDefaultSubstitute(pattern);
+
+            let mut result: c_int = 0;
+            let matched = FcFontMatch(self.raw, pattern, &mut result);
+            FcPatternDestroy(pattern);
+
+            if matched.is_null() {
+                return None;
+            }
+
+            let family = get_string(matched, &object);
+            FcPatternDestroy(matched);
+
+            family
+        }
+    }
+}
+
+impl Drop for Config {
+    /// Releases the configuration
+    fn drop(&mut self) {
+        unsafe {
+            FcConfigDestroy(self.raw);
+        }
+    }
+}
+
+/// Returns the first string value of a pattern's property
+/// # Arguments
+/// * `pattern` - The pattern to read from
+/// * `object` - Name of the property
+unsafe fn get_string(pattern: *const FcPattern, object: &CStr) -> Option<String> {
+    let mut value: *mut c_uchar = std::ptr::null_mut();
+
+    if FcPatternGetString(pattern, object.as_ptr(), 0, &mut value) != FC_RESULT_MATCH
+        || value.is_null()
+    {
+        return None;
+    }
+
+    Some(
+        CStr::from_ptr(value as *const c_char)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
@@ -0,0 +1,13 @@
+//! Fonts bundled with the library, embedded in the program so they can be used from any working
+//! directory
+//!
+//! Pass them to `TextRenderer::from_memory` or `TextRenderer::add_font_data`.
+
+/// Kotta One, a serif display face
+pub const KOTTA_ONE: &[u8] = include_bytes!("fonts/KottaOne.ttf");
+
+/// Akhir Tahun, a handwritten script face
+pub const AKHIR_TAHUN: &[u8] = include_bytes!("fonts/AkhirTahun.ttf");
+
+/// Pixeletter, a pixel art face
+pub const PIXELETTER: &[u8] = include_bytes!("fonts/Pixeletter.ttf");
@@ -10,19 +10,28 @@ pub fn generate_buffer() -> u32 {
 
     assert_ne!(id, 0);
 
-    return id;
+    id
+}
+
+/// Deletes a buffer from the GPU
+/// # Arguments
+/// * `id` - Buffer ID
+pub fn delete_buffer(id: u32) {
+    unsafe {
+        gl::DeleteBuffers(1, &id);
+    }
 }
 
 /// Sets the vertex data in a buffer
 /// # Arguments
 /// * `id` - Buffer ID
 /// * `data` - Data to upload
-pub fn set_buffer_data(id: u32, data: &Vec<f32>) {
+pub fn set_buffer_data(id: u32, data: &[f32]) {
     unsafe {
         gl::BindBuffer(gl::ARRAY_BUFFER, id);
         gl::BufferData(
             gl::ARRAY_BUFFER,
-            (data.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+            std::mem::size_of_val(data) as gl::types::GLsizeiptr,
             data.as_ptr() as *const gl::types::GLvoid,
             gl::DYNAMIC_DRAW,
         );
@@ -30,6 +39,44 @@ pub fn set_buffer_data(id: u32, data: &Vec<f32>) {
     };
 }
 
+/// Allocates uninitialized storage for a buffer, discarding any previous contents
+///
+/// Reallocating a buffer that is still in use lets the driver hand out new memory instead of
+/// waiting for pending draws to finish (buffer orphaning).
+/// # Arguments
+/// * `id` - Buffer ID
+/// * `size` - Size of the buffer in bytes
+pub fn allocate_buffer(id: u32, size: usize) {
+    unsafe {
+        gl::BindBuffer(gl::ARRAY_BUFFER, id);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            size as gl::types::GLsizeiptr,
+            std::ptr::null(),
+            gl::STREAM_DRAW,
+        );
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    };
+}
+
+/// Copies vertex data into part of a buffer without reallocating it
+/// # Arguments
+/// * `id` - Buffer ID
+/// * `offset` - Offset into the buffer in bytes
+/// * `data` - Data to upload
+pub fn set_buffer_sub_data(id: u32, offset: usize, data: &[f32]) {
+    unsafe {
+        gl::BindBuffer(gl::ARRAY_BUFFER, id);
+        gl::BufferSubData(
+            gl::ARRAY_BUFFER,
+            offset as gl::types::GLintptr,
+            std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+            data.as_ptr() as *const gl::types::GLvoid,
+        );
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    };
+}
+
 /// Bind a buffer
 /// # Arguments
 /// * `id` - Buffer ID
@@ -49,7 +96,16 @@ pub fn generate_vertex_array() -> u32 {
 
     assert_ne!(id, 0);
 
-    return id;
+    id
+}
+
+/// Deletes a vertex attribute array from the GPU
+/// # Arguments
+/// * `id` - Vertex Array ID
+pub fn delete_vertex_array(id: u32) {
+    unsafe {
+        gl::DeleteVertexArrays(1, &id);
+    }
 }
 
 /// Bind an attribute array
@@ -94,7 +150,16 @@ pub fn generate_texture() -> u32 {
 
     assert_ne!(id, 0);
 
-    return id;
+    id
+}
+
+/// Deletes a texture from the GPU
+/// # Arguments
+/// * `id` - Texture ID
+pub fn delete_texture(id: u32) {
+    unsafe {
+        gl::DeleteTextures(1, &id);
+    }
 }
 
 /// Bind a texture
@@ -122,12 +187,20 @@ pub fn draw_triangles(vertex_count: u32) {
     unsafe { gl::DrawArrays(gl::TRIANGLES, 0, vertex_count as i32) }
 }
 
+/// Draw Triangles from part of the bound vertex array
+/// # Arguments
+/// * `first` - Index of the first vertex to draw
+/// * `vertex_count` - Number of vertices to draw
+pub fn draw_triangles_range(first: u32, vertex_count: u32) {
+    unsafe { gl::DrawArrays(gl::TRIANGLES, first as i32, vertex_count as i32) }
+}
+
 /// Set the value of a vec3 uniform
 /// # Arguments
 /// * `uniform` - The name of the uniform to copy data to
 /// * `program` - The shader program in use
 /// * `data` - Data to copy to the uniform
-pub fn set_uniform_float_vec3(uniform: &str, program: u32, data: &Vec<f32>) {
+pub fn set_uniform_float_vec3(uniform: &str, program: u32, data: &[f32]) {
     unsafe {
         let location = gl::GetUniformLocation(program, CString::new(uniform).unwrap().as_ptr());
         gl::Uniform3fv(location, 1, data.as_ptr());
@@ -139,9 +212,350 @@ pub fn set_uniform_float_vec3(uniform: &str, program: u32, data: &Vec<f32>) {
 /// * `uniform` - The name of the uniform to copy data to
 /// * `program` - The shader program in use
 /// * `data` - Data to copy to the uniform
-pub fn set_uniform_float_vec2(uniform: &str, program: u32, data: &Vec<f32>) {
+pub fn set_uniform_float_vec2(uniform: &str, program: u32, data: &[f32]) {
+    set_uniform_float_vec2_at(uniform_location(uniform, program), data);
+}
+
+/// Set the value of a vec2 uniform from its location, found with `uniform_location`
+/// # Arguments
+/// * `location` - Location of the uniform in the shader program in use
+/// * `data` - Data to copy to the uniform
+pub fn set_uniform_float_vec2_at(location: i32, data: &[f32]) {
     unsafe {
-        let location = gl::GetUniformLocation(program, CString::new(uniform).unwrap().as_ptr());
         gl::Uniform2fv(location, 1, data.as_ptr());
     }
 }
+
+/// Set the value of an int uniform
+/// # Arguments
+/// * `uniform` - The name of the uniform to copy data to
+/// * `program` - The shader program in use
+/// * `value` - Value to copy to the uniform
+pub fn set_uniform_int(uniform: &str, program: u32, value: i32) {
+    set_uniform_int_at(uniform_location(uniform, program), value);
+}
+
+/// Set the value of an int uniform from its location, found with `uniform_location`
+/// # Arguments
+/// * `location` - Location of the uniform in the shader program in use
+/// * `value` - Value to copy to the uniform
+pub fn set_uniform_int_at(location: i32, value: i32) {
+    unsafe {
+        gl::Uniform1i(location, value);
+    }
+}
+
+/// Returns the location of a uniform in a shader program, -1 if the program has no such uniform
+///
+/// Looking up a location is slow, so uniforms set every draw should have their location found
+/// once when the program is created.
+/// # Arguments
+/// * `uniform` - The name of the uniform
+/// * `program` - The shader program
+pub fn uniform_location(uniform: &str, program: u32) -> i32 {
+    unsafe { gl::GetUniformLocation(program, CString::new(uniform).unwrap().as_ptr()) }
+}
+
+/// Returns the current blend factors as source color, destination color, source alpha and
+/// destination alpha
+pub fn blend_function() -> [u32; 4] {
+    let mut factors = [0; 4];
+
+    unsafe {
+        gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut factors[0]);
+        gl::GetIntegerv(gl::BLEND_DST_RGB, &mut factors[1]);
+        gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut factors[2]);
+        gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut factors[3]);
+    }
+
+    [
+        factors[0] as u32,
+        factors[1] as u32,
+        factors[2] as u32,
+        factors[3] as u32,
+    ]
+}
+
+/// Set the blend factors
+/// # Arguments
+/// * `factors` - Source color, destination color, source alpha and destination alpha factors
+pub fn set_blend_function(factors: [u32; 4]) {
+    unsafe {
+        gl::BlendFuncSeparate(factors[0], factors[1], factors[2], factors[3]);
+    }
+}
+
+/// Returns true if fragment shaders can output a second color for dual source blending
+pub fn supports_dual_source_blending() -> bool {
+    let mut count = 0;
+
+    unsafe {
+        gl::GetIntegerv(gl::MAX_DUAL_SOURCE_DRAW_BUFFERS, &mut count);
+    }
+
+    count > 0
+}
+
+/// Set the value of a float uniform
+/// # Arguments
+/// * `uniform` - The name of the uniform to copy data to
+/// * `program` - The shader program in use
+/// * `value` - Value to copy to the uniform
+pub fn set_uniform_float(uniform: &str, program: u32, value: f32) {
+    set_uniform_float_at(uniform_location(uniform, program), value);
+}
+
+/// Set the value of a float uniform from its location, found with `uniform_location`
+/// # Arguments
+/// * `location` - Location of the uniform in the shader program in use
+/// * `value` - Value to copy to the uniform
+pub fn set_uniform_float_at(location: i32, value: f32) {
+    unsafe {
+        gl::Uniform1f(location, value);
+    }
+}
+
+/// Set the value of a vec4 uniform
+/// # Arguments
+/// * `uniform` - The name of the uniform to copy data to
+/// * `program` - The shader program in use
+/// * `data` - Data to copy to the uniform
+pub fn set_uniform_float_vec4(uniform: &str, program: u32, data: &[f32; 4]) {
+    set_uniform_float_vec4_at(uniform_location(uniform, program), data);
+}
+
+/// Set the value of a vec4 uniform from its location, found with `uniform_location`
+/// # Arguments
+/// * `location` - Location of the uniform in the shader program in use
+/// * `data` - Data to copy to the uniform
+pub fn set_uniform_float_vec4_at(location: i32, data: &[f32; 4]) {
+    unsafe {
+        gl::Uniform4fv(location, 1, data.as_ptr());
+    }
+}
+
+/// Returns the current viewport as x, y, width and height
+pub fn viewport() -> [i32; 4] {
+    let mut viewport = [0; 4];
+
+    unsafe {
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+    }
+
+    viewport
+}
+
+/// Set the viewport
+/// # Arguments
+/// * `viewport` - The x, y, width and height of the viewport
+pub fn set_viewport(viewport: [i32; 4]) {
+    unsafe {
+        gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+    }
+}
+
+/// Returns the scissor box as x, y, width and height, None if the scissor test is disabled
+pub fn scissor() -> Option<[i32; 4]> {
+    let mut scissor = [0; 4];
+
+    unsafe {
+        if gl::IsEnabled(gl::SCISSOR_TEST) != gl::TRUE {
+            return None;
+        }
+        gl::GetIntegerv(gl::SCISSOR_BOX, scissor.as_mut_ptr());
+    }
+
+    Some(scissor)
+}
+
+/// Limit drawing to a box of the framebuffer
+/// # Arguments
+/// * `scissor` - The x, y, width and height of the box in pixels, None to draw anywhere
+pub fn set_scissor(scissor: Option<[i32; 4]>) {
+    unsafe {
+        match scissor {
+            Some(scissor) => {
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(scissor[0], scissor[1], scissor[2], scissor[3]);
+            }
+            None => gl::Disable(gl::SCISSOR_TEST),
+        }
+    }
+}
+
+/// Returns true if blending is enabled
+pub fn blending_enabled() -> bool {
+    unsafe { gl::IsEnabled(gl::BLEND) == gl::TRUE }
+}
+
+/// Enable or disable blending
+/// # Arguments
+/// * `enabled` - Whether drawn fragments are blended with the framebuffer
+pub fn set_blending(enabled: bool) {
+    unsafe {
+        if enabled {
+            gl::Enable(gl::BLEND);
+        } else {
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+/// Creates an empty RGBA texture that can be drawn to through a framebuffer
+/// # Arguments
+/// * `id` - Texture ID
+/// * `width` - Width of the texture in pixels
+/// * `height` - Height of the texture in pixels
+pub fn allocate_render_texture(id: u32, width: u32, height: u32) {
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, id);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8 as i32,
+            width as i32,
+            height as i32,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+}
+
+/// Generates a framebuffer on the GPU and returns its id
+pub fn generate_framebuffer() -> u32 {
+    let mut id = 0;
+
+    unsafe {
+        gl::GenFramebuffers(1, &mut id);
+    };
+
+    assert_ne!(id, 0);
+
+    id
+}
+
+/// Deletes a framebuffer from the GPU
+/// # Arguments
+/// * `id` - Framebuffer ID
+pub fn delete_framebuffer(id: u32) {
+    unsafe {
+        gl::DeleteFramebuffers(1, &id);
+    }
+}
+
+/// Bind a framebuffer for drawing and reading, 0 binds the window's framebuffer
+/// # Arguments
+/// * `id` - Framebuffer ID
+pub fn bind_framebuffer(id: u32) {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+    }
+}
+
+/// Returns the id of the bound framebuffer, 0 for the window's framebuffer
+pub fn framebuffer_binding() -> u32 {
+    let mut id = 0;
+
+    unsafe {
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut id);
+    }
+
+    id as u32
+}
+
+/// Attaches a texture to a framebuffer as its color buffer
+///
+/// The framebuffer is left bound.
+/// # Arguments
+/// * `id` - Framebuffer ID
+/// * `texture` - Texture ID
+pub fn attach_texture(id: u32, texture: u32) -> Result<(), String> {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+    }
+
+    check_framebuffer()
+}
+
+/// Generates a renderbuffer on the GPU and returns its id
+pub fn generate_renderbuffer() -> u32 {
+    let mut id = 0;
+
+    unsafe {
+        gl::GenRenderbuffers(1, &mut id);
+    };
+
+    assert_ne!(id, 0);
+
+    id
+}
+
+/// Deletes a renderbuffer from the GPU
+/// # Arguments
+/// * `id` - Renderbuffer ID
+pub fn delete_renderbuffer(id: u32) {
+    unsafe {
+        gl::DeleteRenderbuffers(1, &id);
+    }
+}
+
+/// Allocates storage for a renderbuffer, discarding any previous contents
+/// # Arguments
+/// * `id` - Renderbuffer ID
+/// * `internal_format` - The pixel format, such as `gl::RGBA8` or `gl::DEPTH24_STENCIL8`
+/// * `width` - Width of the renderbuffer in pixels
+/// * `height` - Height of the renderbuffer in pixels
+pub fn allocate_renderbuffer(id: u32, internal_format: u32, width: u32, height: u32) {
+    unsafe {
+        gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+        gl::RenderbufferStorage(
+            gl::RENDERBUFFER,
+            internal_format,
+            width as i32,
+            height as i32,
+        );
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+    }
+}
+
+/// Attaches a renderbuffer to a framebuffer
+///
+/// The framebuffer is left bound.
+/// # Arguments
+/// * `id` - Framebuffer ID
+/// * `attachment` - The attachment point, such as `gl::COLOR_ATTACHMENT0`
+/// * `renderbuffer` - Renderbuffer ID
+pub fn attach_renderbuffer(id: u32, attachment: u32, renderbuffer: u32) -> Result<(), String> {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer);
+    }
+
+    check_framebuffer()
+}
+
+/// Checks that the bound framebuffer can be drawn to
+fn check_framebuffer() -> Result<(), String> {
+    let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+
+    if status == gl::FRAMEBUFFER_COMPLETE {
+        Ok(())
+    } else {
+        Err(format!("Framebuffer is incomplete: status {:#x}", status))
+    }
+}
@@ -0,0 +1,992 @@
+use crate::atlas::{AtlasRegion, GlyphAtlas, PageFormat, UvRect};
+use crate::color::Color;
+use crate::color_glyph::{self, Layer};
+use crate::font::{Font, FontId, FontInstance, LineMetrics};
+use crate::layout::FontMetrics;
+use crate::outline::LineJoin;
+use crate::sdf;
+use crate::variation::{self, Variation, VariationId};
+
+use std::collections::HashMap;
+
+/// A rasterized glyph stored in the glyph atlas
+#[derive(Clone, Copy, Debug)]
+pub struct Character {
+    /// The atlas page holding the glyph bitmap
+    pub page: usize,
+    /// Region of the atlas page holding the glyph bitmap
+    pub uv: UvRect,
+    /// Width and height of the bitmap in pixels
+    pub size: (i32, i32),
+    /// Offset from the pen position to the top left of the bitmap in pixels
+    pub bearing: (i32, i32),
+    /// Horizontal distance to the next pen position in 26.6 fixed point
+    pub advance: i32,
+    /// Empty margin around the glyph's shape included in the bitmap in pixels
+    pub padding: i32,
+}
+
+/// How glyph bitmaps are generated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GlyphMode {
+    /// Antialiased coverage bitmaps, sharpest when drawn at the rasterized size
+    #[default]
+    Coverage,
+    /// Signed distance fields, which stay crisp when scaled, rotated or zoomed
+    DistanceField,
+    /// Separate coverage for each color subpixel of an LCD panel
+    Subpixel(SubpixelLayout),
+}
+
+/// The arrangement of the color subpixels of a display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubpixelLayout {
+    /// Red, green and blue stripes from left to right
+    Rgb,
+    /// Blue, green and red stripes from left to right
+    Bgr,
+    /// Red, green and blue stripes from top to bottom
+    VerticalRgb,
+    /// Blue, green and red stripes from top to bottom
+    VerticalBgr,
+}
+
+/// Largest distance stored in a distance field glyph, in pixels
+const DISTANCE_FIELD_SPREAD: u32 = 4;
+
+/// Longest a mitered corner of an outline may extend, as a multiple of the outline's width, in
+/// 16.16 fixed point
+const MITER_LIMIT: i64 = 4 << 16;
+
+/// Rasterizes glyphs of a set of fonts on first use and keeps them in a shared glyph atlas
+///
+/// Glyphs are keyed by their font, size, variation and glyph index, so every glyph of every font
+/// can be drawn at any size and, for variable fonts, with any axis values.
+pub struct GlyphCache {
+    fonts: Vec<Font>,
+    dpi: (u32, u32),
+    atlas: GlyphAtlas,
+    mode: GlyphMode,
+    glyphs: HashMap<(FontInstance, u32), Character>,
+    /// Outlines of glyphs, keyed by glyph, width in 26.6 fixed point and join
+    strokes: HashMap<(FontInstance, u32, i32, LineJoin), Character>,
+    char_indices: HashMap<(FontId, char), u32>,
+    variations: Vec<Vec<Variation>>,
+    variation_ids: HashMap<(FontId, Vec<i32>), VariationId>,
+    solid: Option<AtlasRegion>,
+}
+
+impl GlyphCache {
+    /// Creates an empty glyph cache without any fonts
+    /// # Arguments
+    /// * `atlas` - The atlas to store the glyph bitmaps in
+    /// * `mode` - How glyph bitmaps are generated
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    pub fn new(atlas: GlyphAtlas, mode: GlyphMode, dpi: (u32, u32)) -> GlyphCache {
+        GlyphCache {
+            fonts: vec![],
+            dpi,
+            atlas,
+            mode,
+            glyphs: HashMap::new(),
+            strokes: HashMap::new(),
+            char_indices: HashMap::new(),
+            // The first variation is every font's default design
+            variations: vec![vec![]],
+            variation_ids: HashMap::new(),
+            solid: None,
+        }
+    }
+
+    /// Adds a font glyphs can be rasterized from
+    /// # Arguments
+    /// * `font` - The font to add
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// Returns all fonts in the cache, indexed by their ID
+    pub fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    /// Returns a font in the cache
+    /// # Arguments
+    /// * `font` - ID of the font
+    pub fn font(&self, font: FontId) -> &Font {
+        &self.fonts[font.0]
+    }
+
+    /// Returns the resolution of the display glyphs are rasterized for
+    pub fn dpi(&self) -> (u32, u32) {
+        self.dpi
+    }
+
+    /// Returns how glyph bitmaps are generated
+    pub fn mode(&self) -> GlyphMode {
+        self.mode
+    }
+
+    /// Returns the ID of a set of axis values for a font, so glyphs of the font can be
+    /// rasterized and cached with them
+    ///
+    /// Axis values are clamped to the font's axes and rounded to whole design units, or to a
+    /// hundredth of narrower axes, and settings for axes the font does not have are ignored, so
+    /// settings that produce nearly the same design share an ID and animating an axis only
+    /// creates a bounded number of variations. Fonts that do not vary always use the default ID.
+    /// # Arguments
+    /// * `font` - The font the axis values are for
+    /// * `settings` - The requested axis values, later settings of an axis take priority
+    pub fn variation(&mut self, font: FontId, settings: &[Variation]) -> VariationId {
+        let axes = self.fonts[font.0].axes();
+        let resolved = variation::resolve(axes, settings)
+            .into_iter()
+            .zip(axes)
+            .map(|(value, axis)| Variation {
+                tag: value.tag,
+                value: variation::quantize(axis, value.value),
+            })
+            .collect::<Vec<_>>();
+
+        let is_default = axes.iter().zip(resolved.iter()).all(|(axis, value)| {
+            variation::to_fixed(axis.default) == variation::to_fixed(value.value)
+        });
+        if is_default {
+            return VariationId::default();
+        }
+
+        let key = (
+            font,
+            resolved
+                .iter()
+                .map(|value| variation::to_fixed(value.value))
+                .collect::<Vec<_>>(),
+        );
+        let variations = &mut self.variations;
+        *self.variation_ids.entry(key).or_insert_with(|| {
+            variations.push(resolved);
+            VariationId(variations.len() as u32 - 1)
+        })
+    }
+
+    /// Returns the axis values of a variation, empty for the default design
+    /// # Arguments
+    /// * `variation` - ID of the variation
+    pub fn variation_values(&self, variation: VariationId) -> &[Variation] {
+        &self.variations[variation.0 as usize]
+    }
+
+    /// Returns the vertical metrics of a font at a size
+    /// # Arguments
+    /// * `instance` - The font and size
+    pub fn metrics(&mut self, instance: FontInstance) -> FontMetrics {
+        match self.select(instance) {
+            Ok(font) => font.metrics(),
+            Err(_) => FontMetrics::default(),
+        }
+    }
+
+    /// Returns the position and thickness of underlines for a font at a size
+    /// # Arguments
+    /// * `instance` - The font and size
+    pub fn underline(&self, instance: FontInstance) -> LineMetrics {
+        self.font(instance.font)
+            .underline(instance.pixels_per_em(self.dpi).1)
+    }
+
+    /// Returns the position and thickness of strikethrough lines for a font at a size
+    /// # Arguments
+    /// * `instance` - The font and size
+    pub fn strikeout(&self, instance: FontInstance) -> LineMetrics {
+        self.font(instance.font)
+            .strikeout(instance.pixels_per_em(self.dpi).1)
+    }
+
+    /// Returns the atlas glyph bitmaps are stored in
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    /// Returns the glyph index of a character, 0 if the face does not contain it
+    ///
+    /// Lookups are cached per font and code point.
+    /// # Arguments
+    /// * `font` - The font to look in
+    /// * `c` - The character to look up
+    pub fn glyph_index(&mut self, font: FontId, c: char) -> u32 {
+        let fonts = &self.fonts;
+        *self
+            .char_indices
+            .entry((font, c))
+            .or_insert_with(|| fonts[font.0].face().get_char_index(c as usize))
+    }
+
+    /// Returns true if a font has a glyph for every visible character of a piece of text
+    ///
+    /// Control characters and invisible formatting characters, such as joiners and variation
+    /// selectors, are not required to have a glyph.
+    /// # Arguments
+    /// * `font` - The font to look in
+    /// * `text` - The characters to look up
+    pub fn covers(&mut self, font: FontId, text: &str) -> bool {
+        text.chars()
+            .filter(|&c| !is_invisible(c))
+            .all(|c| self.glyph_index(font, c) != 0)
+    }
+
+    /// Returns the first font of a fallback chain that has glyphs for a piece of text
+    ///
+    /// If no font covers all of the text, the first font with a glyph for its first character is
+    /// used, such as a base letter with an unsupported combining mark. Otherwise the first font
+    /// is returned, so its missing glyph is drawn in place of the text.
+    /// # Arguments
+    /// * `chain` - The fonts to try, in order of preference, which must not be empty
+    /// * `text` - The text to draw, usually a single grapheme cluster
+    pub fn fallback(&mut self, chain: &[FontId], text: &str) -> FontId {
+        for &font in chain.iter() {
+            if self.covers(font, text) {
+                return font;
+            }
+        }
+
+        if let Some(first) = text.chars().next() {
+            for &font in chain.iter() {
+                if self.covers(font, first.encode_utf8(&mut [0; 4])) {
+                    return font;
+                }
+            }
+        }
+
+        chain[0]
+    }
+
+    /// Returns the horizontal kerning adjustment between two glyphs in pixels
+    ///
+    /// Only the legacy `kern` table is consulted, fonts that kern through GPOS return 0.
+    /// # Arguments
+    /// * `instance` - The font and size of the glyphs
+    /// * `left` - Index of the first glyph
+    /// * `right` - Index of the glyph following it
+    pub fn kerning(&mut self, instance: FontInstance, left: u32, right: u32) -> f32 {
+        if !self.font(instance.font).face().has_kerning() {
+            return 0.0;
+        }
+        let font = match self.select(instance) {
+            Ok(font) => font,
+            Err(_) => return 0.0,
+        };
+
+        match font
+            .face()
+            .get_kerning(left, right, freetype::face::KerningMode::KerningDefault)
+        {
+            Ok(kerning) => kerning.x as f32 / 64.0 * font.bitmap_scale(),
+            Err(_) => 0.0,
+        }
+    }
+
+    /// Returns a glyph's vertical advance and the offset from its vertical origin to its
+    /// horizontal origin in pixels, as used to set it upright in vertical text
+    ///
+    /// The metrics come from the font's `vhea` and `vmtx` tables, Freetype synthesizes them for
+    /// fonts without those tables. The vertical origin is centered above the glyph.
+    /// # Arguments
+    /// * `instance` - The font and size of the glyph
+    /// * `glyph_index` - Index of the glyph in the face
+    pub fn vertical_metrics(
+        &mut self,
+        instance: FontInstance,
+        glyph_index: u32,
+    ) -> Result<(f32, (f32, f32)), String> {
+        let font = self.select(instance)?;
+        let face = font.face();
+
+        if let Err(message) = face.load_glyph(glyph_index, freetype::face::LoadFlag::NO_BITMAP) {
+            return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+        }
+
+        // Metrics are expressed in 26.6 fixed point
+        let metrics = face.glyph().metrics();
+        let scale = font.bitmap_scale() / 64.0;
+
+        // The bearings locate the top left of the glyph from each origin, the vertical bearing
+        // measuring downwards
+        let offset = (
+            (metrics.vertBearingX - metrics.horiBearingX) as f32 * scale,
+            -(metrics.vertBearingY + metrics.horiBearingY) as f32 * scale,
+        );
+
+        Ok((metrics.vertAdvance as f32 * scale, offset))
+    }
+
+    /// Returns a glyph, rasterizing it into the atlas if it has not been used before
+    /// # Arguments
+    /// * `instance` - The font and size of the glyph
+    /// * `glyph_index` - Index of the glyph in the face
+    pub fn get(&mut self, instance: FontInstance, glyph_index: u32) -> Result<&Character, String> {
+        let key = (instance, glyph_index);
+        if !self.glyphs.contains_key(&key) {
+            let character = self.rasterize(instance, glyph_index)?;
+            self.glyphs.insert(key, character);
+        }
+
+        Ok(&self.glyphs[&key])
+    }
+
+    /// Returns the outline of a glyph, rasterizing it into the atlas if it has not been used before
+    ///
+    /// The outline covers the glyph and extends past its edges by the width, so it is drawn
+    /// behind the glyph. Color glyphs are not outlined.
+    /// # Arguments
+    /// * `stroker` - The Freetype stroker used to outline glyphs
+    /// * `instance` - The font and size of the glyph
+    /// * `glyph_index` - Index of the glyph in the face
+    /// * `width` - Distance the outline extends past the glyph's edge, in pixels
+    /// * `join` - How corners of the outline are shaped
+    pub fn stroked(
+        &mut self,
+        stroker: &freetype::Stroker,
+        instance: FontInstance,
+        glyph_index: u32,
+        width: f32,
+        join: LineJoin,
+    ) -> Result<&Character, String> {
+        let key = (instance, glyph_index, (width * 64.0).round() as i32, join);
+        if !self.strokes.contains_key(&key) {
+            let character = self.rasterize_stroke(stroker, instance, glyph_index, key.2, join)?;
+            self.strokes.insert(key, character);
+        }
+
+        Ok(&self.strokes[&key])
+    }
+
+    /// Returns a fully covered region of the atlas, used to draw solid rectangles such as
+    /// underlines with the same shader and batch as the glyphs
+    pub fn solid(&mut self) -> Result<AtlasRegion, String> {
+        if let Some(region) = self.solid {
+            return Ok(region);
+        }
+
+        let format = match self.mode {
+            GlyphMode::Subpixel(_) => PageFormat::Rgb,
+            GlyphMode::Coverage | GlyphMode::DistanceField => PageFormat::Alpha,
+        };
+
+        // Sample only the center of a small block so filtering never reaches the empty padding
+        let size = 4;
+        let data = vec![255u8; (size * size * format.channels()) as usize];
+        let mut region = self.atlas.insert(format, size, size, &data)?;
+
+        let inset_u = (region.uv.u1 - region.uv.u0) / 4.0;
+        let inset_v = (region.uv.v1 - region.uv.v0) / 4.0;
+        region.uv = UvRect {
+            u0: region.uv.u0 + inset_u,
+            v0: region.uv.v0 + inset_v,
+            u1: region.uv.u1 - inset_u,
+            v1: region.uv.v1 - inset_v,
+        };
+
+        self.solid = Some(region);
+        Ok(region)
+    }
+
+    /// Renders a glyph and copies its bitmap into the atlas
+    /// # Arguments
+    /// * `instance` - The font and size of the glyph
+    /// * `glyph_index` - Index of the glyph in the face
+    fn rasterize(&mut self, instance: FontInstance, glyph_index: u32) -> Result<Character, String> {
+        self.select(instance)?;
+
+        let font = &self.fonts[instance.font.0];
+        let face = font.face();
+        let atlas = &mut self.atlas;
+
+        // Color glyphs are drawn the same way whatever the mode
+        if let Some(layers) = font.color_layers(glyph_index) {
+            return rasterize_layers(face, atlas, glyph_index, &layers);
+        }
+        if font.has_color_bitmaps() {
+            let scale = font.bitmap_scale();
+            if let Some(character) = rasterize_color_bitmap(face, atlas, glyph_index, scale)? {
+                return Ok(character);
+            }
+        }
+
+        match self.mode {
+            GlyphMode::Coverage => rasterize_coverage(face, atlas, glyph_index),
+            GlyphMode::DistanceField => rasterize_distance_field(face, atlas, glyph_index),
+            GlyphMode::Subpixel(layout) => rasterize_subpixel(face, atlas, glyph_index, layout),
+        }
+    }
+
+    /// Renders the outline of a glyph and copies its bitmap into the atlas
+    /// # Arguments
+    /// * `stroker` - The Freetype stroker used to outline glyphs
+    /// * `instance` - The font and size of the glyph
+    /// * `glyph_index` - Index of the glyph in the face
+    /// * `width` - Distance the outline extends past the glyph's edge, in 26.6 fixed point
+    /// * `join` - How corners of the outline are shaped
+    fn rasterize_stroke(
+        &mut self,
+        stroker: &freetype::Stroker,
+        instance: FontInstance,
+        glyph_index: u32,
+        width: i32,
+        join: LineJoin,
+    ) -> Result<Character, String> {
+        let mode = self.mode;
+        self.select(instance)?;
+
+        let font = &self.fonts[instance.font.0];
+
+        let empty = Character {
+            page: 0,
+            uv: UvRect::default(),
+            size: (0, 0),
+            bearing: (0, 0),
+            advance: 0,
+            padding: 0,
+        };
+        if font.color_layers(glyph_index).is_some() || font.has_color_bitmaps() {
+            return Ok(empty);
+        }
+
+        // Load the outline the same way as the glyph it is drawn behind
+        let flags = freetype::face::LoadFlag::NO_BITMAP
+            | match mode {
+                GlyphMode::Coverage => freetype::face::LoadFlag::DEFAULT,
+                GlyphMode::DistanceField => freetype::face::LoadFlag::NO_HINTING,
+                GlyphMode::Subpixel(layout) if is_vertical(layout) => {
+                    freetype::face::LoadFlag::TARGET_LCD_V
+                }
+                GlyphMode::Subpixel(_) => freetype::face::LoadFlag::TARGET_LCD,
+            };
+        if let Err(message) = font.face().load_glyph(glyph_index, flags) {
+            return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+        }
+
+        let slot = font.face().glyph();
+        let advance = slot.advance().x as i32;
+        let atlas = &mut self.atlas;
+
+        // Distance fields are grown from the glyph's own outline
+        if mode == GlyphMode::DistanceField {
+            let expand = width as f32 / 64.0;
+            let field = match slot.outline() {
+                Some(outline) => {
+                    sdf::from_outline_expanded(&outline, DISTANCE_FIELD_SPREAD, expand)
+                }
+                None => None,
+            };
+
+            return match field {
+                Some(field) => {
+                    let region =
+                        atlas.insert(PageFormat::Alpha, field.width, field.height, &field.data)?;
+
+                    Ok(Character {
+                        page: region.page,
+                        uv: region.uv,
+                        size: (field.width as i32, field.height as i32),
+                        bearing: (field.left, field.top),
+                        advance,
+                        padding: DISTANCE_FIELD_SPREAD as i32,
+                    })
+                }
+                None => Ok(Character { advance, ..empty }),
+            };
+        }
+
+        let glyph = match slot.get_glyph() {
+            Ok(glyph) => glyph,
+            Err(message) => {
+                return Err(format!("Unable to load glyph {}: {}", glyph_index, message))
+            }
+        };
+        if glyph.format() != freetype::ffi::FT_GLYPH_FORMAT_OUTLINE {
+            return Ok(Character { advance, ..empty });
+        }
+
+        // The outer border of the stroke covers the glyph and the outline around it
+        let line_join = match join {
+            LineJoin::Round => freetype::StrokerLineJoin::Round,
+            LineJoin::Bevel => freetype::StrokerLineJoin::Bevel,
+            LineJoin::Miter => freetype::StrokerLineJoin::MiterFixed,
+        };
+        stroker.set(
+            width as freetype::ffi::FT_Fixed,
+            freetype::StrokerLineCap::Round,
+            line_join,
+            MITER_LIMIT as freetype::ffi::FT_Fixed,
+        );
+        let stroke = match glyph.stroke_border(stroker, false) {
+            Ok(stroke) => stroke,
+            Err(message) => {
+                return Err(format!(
+                    "Unable to outline glyph {}: {}",
+                    glyph_index, message
+                ))
+            }
+        };
+
+        let render_mode = match mode {
+            GlyphMode::Subpixel(layout) if is_vertical(layout) => freetype::RenderMode::LcdV,
+            GlyphMode::Subpixel(_) => freetype::RenderMode::Lcd,
+            _ => freetype::RenderMode::Normal,
+        };
+        let rendered = match stroke.to_bitmap(render_mode, None) {
+            Ok(rendered) => rendered,
+            Err(message) => {
+                return Err(format!(
+                    "Unable to render glyph {}: {}",
+                    glyph_index, message
+                ))
+            }
+        };
+
+        let bitmap = rendered.bitmap();
+        let (format, width, height, data) = match mode {
+            GlyphMode::Subpixel(layout) => {
+                let (width, height, data) = pack_subpixels(&bitmap, layout);
+                (PageFormat::Rgb, width, height, data)
+            }
+            _ => {
+                let (width, height) = (bitmap.width() as usize, bitmap.rows() as usize);
+                (PageFormat::Alpha, width, height, pack_rows(&bitmap, width))
+            }
+        };
+
+        // Empty outlines take up no space
+        let (page, uv) = if width > 0 && height > 0 {
+            let region = atlas.insert(format, width as u32, height as u32, &data)?;
+            (region.page, region.uv)
+        } else {
+            (0, UvRect::default())
+        };
+
+        Ok(Character {
+            page,
+            uv,
+            size: (width as i32, height as i32),
+            bearing: (rendered.left(), rendered.top()),
+            advance,
+            padding: 0,
+        })
+    }
+
+    /// Prepares a font's face to load glyphs at the size and variation of an instance
+    /// # Arguments
+    /// * `instance` - The font, size and variation to load glyphs with
+    fn select(&mut self, instance: FontInstance) -> Result<&mut Font, String> {
+        let font = &mut self.fonts[instance.font.0];
+        font.set_variation(&self.variations[instance.variation.0 as usize])?;
+        font.set_size(instance.size, self.dpi)?;
+
+        Ok(font)
+    }
+}
+
+/// Returns true for characters that are never drawn on their own
+/// # Arguments
+/// * `c` - The character to check
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        // Zero width spaces, joiners and directional marks
+        || ('\u{200B}'..='\u{200F}').contains(&c)
+        || ('\u{202A}'..='\u{202E}').contains(&c)
+        || ('\u{2060}'..='\u{2064}').contains(&c)
+        // Variation selectors
+        || ('\u{FE00}'..='\u{FE0F}').contains(&c)
+        || ('\u{E0100}'..='\u{E01EF}').contains(&c)
+        || c == '\u{FEFF}'
+}
+
+/// Renders a glyph's coverage bitmap and copies it into the atlas
+/// # Arguments
+/// * `face` - The face to load the glyph from, with its size already set
+/// * `atlas` - The atlas to store the bitmap in
+/// * `glyph_index` - Index of the glyph in the face
+fn rasterize_coverage(
+    face: &freetype::Face,
+    atlas: &mut GlyphAtlas,
+    glyph_index: u32,
+) -> Result<Character, String> {
+    if let Err(message) = face.load_glyph(glyph_index, freetype::face::LoadFlag::RENDER) {
+        return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+    }
+
+    let glyph = face.glyph();
+    let bitmap = glyph.bitmap();
+
+    // Copy the glyphs bitmap into the atlas without the padding at the end of its rows, empty
+    // glyphs take up no space
+    let (page, uv) = if bitmap.width() > 0 && bitmap.rows() > 0 {
+        let width = bitmap.width() as usize;
+        let region = atlas.insert(
+            PageFormat::Alpha,
+            width as u32,
+            bitmap.rows() as u32,
+            &pack_rows(&bitmap, width),
+        )?;
+        (region.page, region.uv)
+    } else {
+        (0, UvRect::default())
+    };
+
+    Ok(Character {
+        page,
+        uv,
+        size: (bitmap.width(), bitmap.rows()),
+        bearing: (glyph.bitmap_left(), glyph.bitmap_top()),
+        advance: glyph.advance().x as i32,
+        padding: 0,
+    })
+}
+
+/// Generates a signed distance field from a glyph's outline and copies it into the atlas
+/// # Arguments
+/// * `face` - The face to load the glyph from, with its size already set
+/// * `atlas` - The atlas to store the bitmap in
+/// * `glyph_index` - Index of the glyph in the face
+fn rasterize_distance_field(
+    face: &freetype::Face,
+    atlas: &mut GlyphAtlas,
+    glyph_index: u32,
+) -> Result<Character, String> {
+    // Hinting distorts the outline to fit the pixel grid at one size, which defeats scaling
+    let flags = freetype::face::LoadFlag::NO_HINTING | freetype::face::LoadFlag::NO_BITMAP;
+    if let Err(message) = face.load_glyph(glyph_index, flags) {
+        return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+    }
+
+    let glyph = face.glyph();
+    let advance = glyph.advance().x as i32;
+
+    // Glyphs without an outline, such as spaces, take up no space
+    let field = match glyph.outline() {
+        Some(outline) => sdf::from_outline(&outline, DISTANCE_FIELD_SPREAD),
+        None => None,
+    };
+
+    match field {
+        Some(field) => {
+            let region = atlas.insert(PageFormat::Alpha, field.width, field.height, &field.data)?;
+
+            Ok(Character {
+                page: region.page,
+                uv: region.uv,
+                size: (field.width as i32, field.height as i32),
+                bearing: (field.left, field.top),
+                advance,
+                padding: DISTANCE_FIELD_SPREAD as i32,
+            })
+        }
+        None => Ok(Character {
+            page: 0,
+            uv: UvRect::default(),
+            size: (0, 0),
+            bearing: (0, 0),
+            advance,
+            padding: 0,
+        }),
+    }
+}
+
+/// Renders a glyph with separate coverage for each subpixel and copies it into the atlas
+/// # Arguments
+/// * `face` - The face to load the glyph from, with its size already set
+/// * `atlas` - The atlas to store the bitmap in
+/// * `glyph_index` - Index of the glyph in the face
+/// * `layout` - The arrangement of the display's subpixels
+fn rasterize_subpixel(
+    face: &freetype::Face,
+    atlas: &mut GlyphAtlas,
+    glyph_index: u32,
+    layout: SubpixelLayout,
+) -> Result<Character, String> {
+    let (flags, render_mode) = if is_vertical(layout) {
+        (
+            freetype::face::LoadFlag::TARGET_LCD_V,
+            freetype::RenderMode::LcdV,
+        )
+    } else {
+        (
+            freetype::face::LoadFlag::TARGET_LCD,
+            freetype::RenderMode::Lcd,
+        )
+    };
+
+    if let Err(message) = face.load_glyph(glyph_index, flags) {
+        return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+    }
+
+    let glyph = face.glyph();
+    if let Err(message) = glyph.render_glyph(render_mode) {
+        return Err(format!(
+            "Unable to render glyph {}: {}",
+            glyph_index, message
+        ));
+    }
+
+    let (width, height, data) = pack_subpixels(&glyph.bitmap(), layout);
+
+    // Empty glyphs take up no space
+    let (page, uv) = if width > 0 && height > 0 {
+        let region = atlas.insert(PageFormat::Rgb, width as u32, height as u32, &data)?;
+        (region.page, region.uv)
+    } else {
+        (0, UvRect::default())
+    };
+
+    Ok(Character {
+        page,
+        uv,
+        size: (width as i32, height as i32),
+        bearing: (glyph.bitmap_left(), glyph.bitmap_top()),
+        advance: glyph.advance().x as i32,
+        padding: 0,
+    })
+}
+
+/// Returns true if a display's subpixels are arranged from top to bottom
+/// # Arguments
+/// * `layout` - The arrangement of the display's subpixels
+fn is_vertical(layout: SubpixelLayout) -> bool {
+    match layout {
+        SubpixelLayout::Rgb | SubpixelLayout::Bgr => false,
+        SubpixelLayout::VerticalRgb | SubpixelLayout::VerticalBgr => true,
+    }
+}
+
+/// Copies the rows of a bitmap without the padding at the end of each row
+/// # Arguments
+/// * `bitmap` - The bitmap to copy
+/// * `row_size` - Number of bytes of each row to keep
+fn pack_rows(bitmap: &freetype::Bitmap, row_size: usize) -> Vec<u8> {
+    let pitch = bitmap.pitch().unsigned_abs() as usize;
+    if pitch == 0 {
+        return vec![];
+    }
+
+    bitmap
+        .buffer()
+        .chunks(pitch)
+        .take(bitmap.rows() as usize)
+        .flat_map(|row| row[..row_size].iter().copied())
+        .collect()
+}
+
+/// Gathers the separate subpixel samples of an LCD bitmap into red, green and blue pixels
+///
+/// Returns the width and height of the image in pixels, and its tightly packed data.
+/// # Arguments
+/// * `bitmap` - A bitmap rendered for an LCD display
+/// * `layout` - The arrangement of the display's subpixels
+fn pack_subpixels(bitmap: &freetype::Bitmap, layout: SubpixelLayout) -> (usize, usize, Vec<u8>) {
+    // Freetype stores each subpixel as its own sample, three times wider or taller than the
+    // glyph, with rows padded to the bitmap's pitch
+    let vertical = is_vertical(layout);
+    let pitch = bitmap.pitch().unsigned_abs() as usize;
+    let buffer = bitmap.buffer();
+    let (width, height) = if vertical {
+        (bitmap.width() as usize, bitmap.rows() as usize / 3)
+    } else {
+        (bitmap.width() as usize / 3, bitmap.rows() as usize)
+    };
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let mut samples = if vertical {
+                [
+                    buffer[y * 3 * pitch + x],
+                    buffer[(y * 3 + 1) * pitch + x],
+                    buffer[(y * 3 + 2) * pitch + x],
+                ]
+            } else {
+                [
+                    buffer[y * pitch + x * 3],
+                    buffer[y * pitch + x * 3 + 1],
+                    buffer[y * pitch + x * 3 + 2],
+                ]
+            };
+
+            if let SubpixelLayout::Bgr | SubpixelLayout::VerticalBgr = layout {
+                samples.reverse();
+            }
+
+            data.extend_from_slice(&samples);
+        }
+    }
+
+    (width, height, data)
+}
+
+/// Draws a layered color glyph by filling each layer's coverage with its color, and copies the
+/// result into the atlas
+///
+/// Layers filled with the foreground color are drawn black, as the text color is only known
+/// once the glyph is drawn.
+/// # Arguments
+/// * `face` - The face to load the layer glyphs from, with its size already set
+/// * `atlas` - The atlas to store the image in
+/// * `glyph_index` - Index of the color glyph in the face
+/// * `layers` - The layers of the glyph, bottom layer first
+fn rasterize_layers(
+    face: &freetype::Face,
+    atlas: &mut GlyphAtlas,
+    glyph_index: u32,
+    layers: &[Layer],
+) -> Result<Character, String> {
+    // The advance comes from the color glyph itself
+    if let Err(message) = face.load_glyph(glyph_index, freetype::face::LoadFlag::DEFAULT) {
+        return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+    }
+    let advance = face.glyph().advance().x as i32;
+
+    // Render every layer, keeping its coverage and position relative to the origin
+    let mut rendered = vec![];
+    for layer in layers.iter() {
+        let flags = freetype::face::LoadFlag::RENDER;
+        if let Err(message) = face.load_glyph(layer.glyph_index, flags) {
+            return Err(format!(
+                "Unable to load glyph {}: {}",
+                layer.glyph_index, message
+            ));
+        }
+
+        let glyph = face.glyph();
+        let bitmap = glyph.bitmap();
+        if bitmap.width() == 0 || bitmap.rows() == 0 {
+            continue;
+        }
+
+        let coverage = pack_rows(&bitmap, bitmap.width() as usize);
+
+        rendered.push((
+            (glyph.bitmap_left(), glyph.bitmap_top()),
+            (bitmap.width(), bitmap.rows()),
+            coverage,
+            layer.color.unwrap_or(Color::BLACK),
+        ));
+    }
+
+    // The image covers every layer
+    let left = rendered.iter().map(|layer| layer.0 .0).min().unwrap_or(0);
+    let top = rendered.iter().map(|layer| layer.0 .1).max().unwrap_or(0);
+    let right = rendered
+        .iter()
+        .map(|layer| layer.0 .0 + layer.1 .0)
+        .max()
+        .unwrap_or(0);
+    let bottom = rendered
+        .iter()
+        .map(|layer| layer.0 .1 - layer.1 .1)
+        .min()
+        .unwrap_or(0);
+    let (width, height) = ((right - left).max(0), (top - bottom).max(0));
+
+    // Composite the layers from the bottom up, in premultiplied alpha
+    let mut image = vec![0.0f32; (width * height * 4) as usize];
+    for ((layer_left, layer_top), (layer_width, layer_rows), coverage, color) in rendered.iter() {
+        let offset_x = layer_left - left;
+        let offset_y = top - layer_top;
+
+        for y in 0..*layer_rows {
+            for x in 0..*layer_width {
+                let alpha = color.a * coverage[(y * layer_width + x) as usize] as f32 / 255.0;
+                let i = (((offset_y + y) * width + offset_x + x) * 4) as usize;
+
+                let source = [color.r * alpha, color.g * alpha, color.b * alpha, alpha];
+                for (channel, value) in source.iter().enumerate() {
+                    image[i + channel] = value + image[i + channel] * (1.0 - alpha);
+                }
+            }
+        }
+    }
+
+    let data = image
+        .iter()
+        .map(|value| (value * 255.0).round() as u8)
+        .collect::<Vec<_>>();
+
+    let (page, uv) = if width > 0 && height > 0 {
+        let region = atlas.insert(PageFormat::Rgba, width as u32, height as u32, &data)?;
+        (region.page, region.uv)
+    } else {
+        (0, UvRect::default())
+    };
+
+    Ok(Character {
+        page,
+        uv,
+        size: (width, height),
+        bearing: (left, top),
+        advance,
+        padding: 0,
+    })
+}
+
+/// Loads a glyph from a color bitmap strike, scales it to the requested size and copies it into
+/// the atlas
+///
+/// Returns None if the face has no color bitmap for the glyph.
+/// # Arguments
+/// * `face` - The face to load the glyph from, with its strike already selected
+/// * `atlas` - The atlas to store the bitmap in
+/// * `glyph_index` - Index of the glyph in the face
+/// * `scale` - Factor to scale the strike's bitmaps by to reach the requested size
+fn rasterize_color_bitmap(
+    face: &freetype::Face,
+    atlas: &mut GlyphAtlas,
+    glyph_index: u32,
+    scale: f32,
+) -> Result<Option<Character>, String> {
+    let flags = freetype::face::LoadFlag::COLOR | freetype::face::LoadFlag::RENDER;
+    if let Err(message) = face.load_glyph(glyph_index, flags) {
+        return Err(format!("Unable to load glyph {}: {}", glyph_index, message));
+    }
+
+    let glyph = face.glyph();
+    let bitmap = glyph.bitmap();
+    match bitmap.pixel_mode() {
+        Ok(freetype::bitmap::PixelMode::Bgra) => {}
+        _ => return Ok(None),
+    }
+
+    // Freetype stores premultiplied blue, green, red and alpha, with rows padded to the pitch
+    let (width, height) = (bitmap.width() as usize, bitmap.rows() as usize);
+    let pitch = bitmap.pitch().unsigned_abs() as usize;
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in bitmap.buffer().chunks(pitch).take(height) {
+        for bgra in row[..width * 4].chunks(4) {
+            data.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+        }
+    }
+
+    let scaled = |value: f32| (value * scale).round() as i32;
+    let size = (scaled(width as f32), scaled(height as f32));
+    if size != (width as i32, height as i32) {
+        data = color_glyph::resample(&data, (width, height), (size.0 as usize, size.1 as usize));
+    }
+
+    let (page, uv) = if size.0 > 0 && size.1 > 0 {
+        let region = atlas.insert(PageFormat::Rgba, size.0 as u32, size.1 as u32, &data)?;
+        (region.page, region.uv)
+    } else {
+        (0, UvRect::default())
+    };
+
+    Ok(Some(Character {
+        page,
+        uv,
+        size,
+        bearing: (
+            scaled(glyph.bitmap_left() as f32),
+            scaled(glyph.bitmap_top() as f32),
+        ),
+        advance: scaled(glyph.advance().x as f32),
+        padding: 0,
+    }))
+}
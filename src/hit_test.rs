@@ -0,0 +1,576 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::layout::{Line, Rect, TextLayout};
+
+use std::ops::Range;
+
+/// The character of laid out text found at a point
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hit {
+    /// Index of the line in the layout
+    pub line: usize,
+    /// Byte range of the grapheme cluster at the point, empty on a line without text
+    pub cluster: Range<usize>,
+    /// True if the point is over the half of the cluster that comes last in reading order, the
+    /// right half of left to right text and the left half of right to left text
+    pub trailing: bool,
+    /// True if the point is over the cluster itself rather than beside or between lines
+    pub inside: bool,
+}
+
+impl Hit {
+    /// Returns the byte offset of the caret position closest to the point, after the cluster if
+    /// the point is over its trailing half and before it otherwise
+    pub fn caret_index(&self) -> usize {
+        if self.trailing {
+            self.cluster.end
+        } else {
+            self.cluster.start
+        }
+    }
+}
+
+/// A grapheme cluster placed on a line
+struct ClusterBox {
+    /// Byte range of the cluster in the text
+    range: Range<usize>,
+    /// Start and end of the cluster along the line, in visual order
+    start: f32,
+    end: f32,
+    /// True if the cluster is written right to left
+    rtl: bool,
+}
+
+impl ClusterBox {
+    /// Returns the position of one of the cluster's edges along the line
+    /// # Arguments
+    /// * `trailing` - Whether to return the edge that comes last in reading order
+    fn edge(&self, trailing: bool) -> f32 {
+        if trailing != self.rtl {
+            self.end
+        } else {
+            self.start
+        }
+    }
+}
+
+/// Converts a position in window coordinates, such as those of SDL mouse events, into the
+/// coordinates text is drawn in
+///
+/// Window coordinates start at the top left corner and increase downwards, while the
+/// projection set by `TextRenderer::set_projection` puts the origin at the center of the window
+/// and increases upwards. The position is taken to be the center of the pixel.
+/// # Arguments
+/// * `position` - Position in pixels from the top left corner of the window
+/// * `window_size` - Width and height of the window, as passed to `set_projection`
+pub fn window_to_view(position: (i32, i32), window_size: (u32, u32)) -> (f32, f32) {
+    (
+        position.0 as f32 + 0.5 - window_size.0 as f32 / 2.0,
+        window_size.1 as f32 / 2.0 - (position.1 as f32 + 0.5),
+    )
+}
+
+/// Finds the grapheme cluster of laid out text at a point
+///
+/// Points between lines hit the closest line, and points beyond either end of a line hit the
+/// cluster at that end. Clusters drawn with a single glyph, such as ligatures, are divided
+/// evenly between their graphemes. Returns None if the layout has no lines.
+/// # Arguments
+/// * `text` - The text the layout was made from
+/// * `layout` - The laid out text
+/// * `point` - The point relative to the layout origin
+pub fn hit_test(text: &str, layout: &TextLayout, point: (f32, f32)) -> Option<Hit> {
+    // The line whose ascender to descender is closest to the point
+    let (line_index, line) = layout
+        .lines
+        .iter()
+        .enumerate()
+        .min_by(|a, b| across_distance(a.1, point).total_cmp(&across_distance(b.1, point)))?;
+    let inside_line = across_distance(line, point) == 0.0;
+
+    let boxes = cluster_boxes(text, line);
+    let (first, last) = match (boxes.first(), boxes.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            return Some(Hit {
+                line: line_index,
+                cluster: line.range.start..line.range.start,
+                trailing: false,
+                inside: false,
+            })
+        }
+    };
+
+    let along = along_line(line, point);
+    let (hit, trailing, inside) = if along < first.start {
+        // Before the line, the visually first edge of a right to left cluster is its trailing edge
+        (first, first.rtl, false)
+    } else if along >= last.end {
+        (last, !last.rtl, false)
+    } else {
+        let hit = boxes
+            .iter()
+            .find(|cluster| along < cluster.end)
+            .unwrap_or(last);
+        let second_half = along >= (hit.start + hit.end) / 2.0;
+        (hit, second_half != hit.rtl, inside_line)
+    };
+
+    Some(Hit {
+        line: line_index,
+        cluster: hit.range.clone(),
+        trailing,
+        inside,
+    })
+}
+
+/// Returns the index of the line a caret at a byte offset is drawn on
+///
+/// A caret at the end of a line that was wrapped is on the next line. Returns None if the
+/// offset is not on any line.
+/// # Arguments
+/// * `layout` - The laid out text
+/// * `index` - Byte offset of the caret in the text
+pub fn line_at(layout: &TextLayout, index: usize) -> Option<usize> {
+    layout
+        .lines
+        .iter()
+        .position(|line| line.range.contains(&index))
+        .or_else(|| layout.lines.iter().position(|line| line.range.end == index))
+}
+
+/// Returns the rectangle to draw a caret in, before the character at a byte offset
+///
+/// The caret spans the line's ascender to descender, or runs across the column in vertical
+/// text. A caret at the end of a line that was wrapped is placed at the start of the next line.
+/// Returns None if the offset is not on any line.
+/// # Arguments
+/// * `text` - The text the layout was made from
+/// * `layout` - The laid out text
+/// * `index` - Byte offset of the caret in the text
+/// * `width` - Thickness of the caret
+pub fn caret_rect(text: &str, layout: &TextLayout, index: usize, width: f32) -> Option<Rect> {
+    let line = &layout.lines[line_at(layout, index)?];
+
+    let boxes = cluster_boxes(text, line);
+
+    // The leading edge of the cluster starting at the caret, or the trailing edge of the one
+    // ending at it at the end of the line
+    let along = match boxes.iter().find(|cluster| cluster.range.contains(&index)) {
+        Some(cluster) => cluster.edge(false),
+        None => match boxes.iter().find(|cluster| cluster.range.end == index) {
+            Some(cluster) => cluster.edge(true),
+            None if line.rtl => line.x + line.width,
+            None => line.x,
+        },
+    };
+
+    let across = (line.descender, line.ascender);
+    Some(line.area((along - width / 2.0, along + width / 2.0), across))
+}
+
+/// Returns the rectangles covering a range of laid out text, such as a selection
+///
+/// Each line gets one rectangle for every visually contiguous part of the range, so a range
+/// of mixed left to right and right to left text can take several rectangles on one line.
+/// # Arguments
+/// * `text` - The text the layout was made from
+/// * `layout` - The laid out text
+/// * `range` - Byte range of the text to cover
+pub fn range_rects(text: &str, layout: &TextLayout, range: Range<usize>) -> Vec<Rect> {
+    let mut rects = vec![];
+    if range.is_empty() {
+        return rects;
+    }
+
+    for line in layout.lines.iter() {
+        let across = (line.descender, line.ascender);
+
+        // Extents of contiguous selected clusters along the line
+        let mut spans: Vec<(f32, f32)> = vec![];
+        let selected = cluster_boxes(text, line)
+            .into_iter()
+            .filter(|cluster| cluster.range.start >= range.start && cluster.range.end <= range.end);
+        for cluster in selected {
+            match spans.last_mut() {
+                Some(span) if (span.1 - cluster.start).abs() < 0.5 => span.1 = cluster.end,
+                _ => spans.push((cluster.start, cluster.end)),
+            }
+        }
+
+        rects.extend(spans.into_iter().map(|span| line.area(span, across)));
+    }
+
+    rects
+}
+
+/// Returns how far a point is from a line across the line's direction, 0 if the point is
+/// between the line's ascender and descender
+/// # Arguments
+/// * `line` - The laid out line
+/// * `point` - The point relative to the layout origin
+fn across_distance(line: &Line, point: (f32, f32)) -> f32 {
+    let across = if line.vertical { point.0 } else { point.1 };
+
+    let bottom = line.baseline + line.descender;
+    let top = line.baseline + line.ascender;
+    (bottom - across).max(across - top).max(0.0)
+}
+
+/// Returns the position of a point along a line, in the same units as the line's `x`
+/// # Arguments
+/// * `line` - The laid out line
+/// * `point` - The point relative to the layout origin
+fn along_line(line: &Line, point: (f32, f32)) -> f32 {
+    if line.vertical {
+        -point.1
+    } else {
+        point.0
+    }
+}
+
+/// Returns the grapheme clusters of a line in visual order, with their extents along the line
+/// # Arguments
+/// * `text` - The text the line was laid out from
+/// * `line` - The laid out line
+fn cluster_boxes(text: &str, line: &Line) -> Vec<ClusterBox> {
+    // Each shaping cluster covers the text up to the next cluster in logical order
+    let mut starts = line
+        .glyphs
+        .iter()
+        .map(|glyph| glyph.cluster)
+        .collect::<Vec<_>>();
+    starts.sort_unstable();
+    starts.dedup();
+
+    // Visual order of the first glyph of each shaping cluster
+    let mut clusters: Vec<(usize, f32, f32, bool)> = vec![];
+    for glyph in line.glyphs.iter() {
+        let (start, end) = line.glyph_extent(glyph);
+        match clusters
+            .iter_mut()
+            .find(|cluster| cluster.0 == glyph.cluster)
+        {
+            Some(cluster) => {
+                cluster.1 = cluster.1.min(start);
+                cluster.2 = cluster.2.max(end);
+            }
+            None => clusters.push((glyph.cluster, start, end, glyph.rtl)),
+        }
+    }
+
+    let mut boxes = vec![];
+    for (cluster, start, end, rtl) in clusters {
+        let next = starts.partition_point(|&start| start <= cluster);
+        let cluster_end = starts.get(next).copied().unwrap_or(line.range.end);
+        let cluster_end = cluster_end.max(cluster).min(text.len());
+
+        // Graphemes share the cluster's advance evenly, in the cluster's direction
+        let graphemes = text[cluster..cluster_end]
+            .grapheme_indices(true)
+            .map(|(i, grapheme)| cluster + i..cluster + i + grapheme.len())
+            .collect::<Vec<_>>();
+        let count = graphemes.len().max(1);
+        let step = (end - start) / count as f32;
+
+        let mut cluster_boxes = graphemes
+            .into_iter()
+            .enumerate()
+            .map(|(i, range)| ClusterBox {
+                range,
+                start: start + step * i as f32,
+                end: start + step * (i + 1) as f32,
+                rtl,
+            })
+            .collect::<Vec<_>>();
+        if rtl {
+            // Reverse the ranges so the first grapheme is on the right
+            let ranges = cluster_boxes
+                .iter()
+                .rev()
+                .map(|cluster| cluster.range.clone())
+                .collect::<Vec<_>>();
+            for (cluster, range) in cluster_boxes.iter_mut().zip(ranges) {
+                cluster.range = range;
+            }
+        }
+
+        boxes.append(&mut cluster_boxes);
+    }
+
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::PositionedGlyph;
+
+    /// Creates a line of glyphs placed left to right in visual order
+    /// # Arguments
+    /// * `range` - Byte range of the line
+    /// * `baseline` - Vertical position of the baseline
+    /// * `glyphs` - Cluster, advance and direction of each glyph
+    /// * `rtl` - Whether the line belongs to a right to left paragraph
+    fn line(range: Range<usize>, baseline: f32, glyphs: &[(usize, f32, bool)], rtl: bool) -> Line {
+        let mut x = 0.0;
+        let glyphs = glyphs
+            .iter()
+            .map(|&(cluster, advance, rtl)| {
+                let glyph = PositionedGlyph {
+                    font: Default::default(),
+                    glyph_index: 0,
+                    cluster,
+                    position: (x, baseline),
+                    offset: (0.0, 0.0),
+                    advance: (advance, 0.0),
+                    rtl,
+                    sideways: false,
+                };
+                x += advance;
+                glyph
+            })
+            .collect();
+
+        Line {
+            range,
+            glyphs,
+            baseline,
+            x: 0.0,
+            width: x,
+            ascender: 8.0,
+            descender: -2.0,
+            rtl,
+            vertical: false,
+        }
+    }
+
+    fn layout(lines: Vec<Line>) -> TextLayout {
+        TextLayout {
+            lines,
+            bounds: Rect::default(),
+        }
+    }
+
+    /// Returns the start and end of rectangles along the x axis
+    fn spans(rects: &[Rect]) -> Vec<(f32, f32)> {
+        rects.iter().map(|r| (r.x, r.x + r.width)).collect()
+    }
+
+    /// "abc", one glyph per character
+    fn ltr() -> TextLayout {
+        let glyphs = [(0, 10.0, false), (1, 10.0, false), (2, 10.0, false)];
+        layout(vec![line(0..3, 0.0, &glyphs, false)])
+    }
+
+    /// Two Hebrew letters of two bytes each, the first is drawn on the right
+    const HEBREW: &str = "\u{5D0}\u{5D1}";
+    fn rtl() -> TextLayout {
+        let glyphs = [(2, 10.0, true), (0, 10.0, true)];
+        layout(vec![line(0..4, 0.0, &glyphs, true)])
+    }
+
+    #[test]
+    fn ltr_leading_and_trailing_halves() {
+        let layout = ltr();
+
+        let hit = hit_test("abc", &layout, (4.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 0..1);
+        assert!(!hit.trailing);
+        assert!(hit.inside);
+        assert_eq!(hit.caret_index(), 0);
+
+        let hit = hit_test("abc", &layout, (16.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 1..2);
+        assert!(hit.trailing);
+        assert_eq!(hit.caret_index(), 2);
+    }
+
+    #[test]
+    fn rtl_leading_and_trailing_halves() {
+        let layout = rtl();
+
+        // The right half of a right to left cluster comes first in reading order
+        let hit = hit_test(HEBREW, &layout, (16.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 0..2);
+        assert!(!hit.trailing);
+        assert_eq!(hit.caret_index(), 0);
+
+        let hit = hit_test(HEBREW, &layout, (12.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 0..2);
+        assert!(hit.trailing);
+        assert_eq!(hit.caret_index(), 2);
+
+        let hit = hit_test(HEBREW, &layout, (3.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 2..4);
+        assert!(hit.trailing);
+        assert_eq!(hit.caret_index(), 4);
+    }
+
+    #[test]
+    fn points_beyond_line_ends() {
+        let layout = ltr();
+
+        let before = hit_test("abc", &layout, (-5.0, 0.0)).unwrap();
+        assert_eq!(before.caret_index(), 0);
+        assert!(!before.inside);
+
+        let after = hit_test("abc", &layout, (100.0, 0.0)).unwrap();
+        assert_eq!(after.caret_index(), 3);
+        assert!(!after.inside);
+
+        // Right to left text ends on the left
+        let layout = rtl();
+        assert_eq!(
+            hit_test(HEBREW, &layout, (-5.0, 0.0))
+                .unwrap()
+                .caret_index(),
+            4
+        );
+        assert_eq!(
+            hit_test(HEBREW, &layout, (100.0, 0.0))
+                .unwrap()
+                .caret_index(),
+            0
+        );
+    }
+
+    #[test]
+    fn points_above_and_below_lines() {
+        let layout = ltr();
+
+        let above = hit_test("abc", &layout, (4.0, 50.0)).unwrap();
+        assert_eq!(above.cluster, 0..1);
+        assert!(!above.inside);
+
+        assert!(hit_test("", &TextLayout::default(), (0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn ligature_split_between_graphemes() {
+        // "ffi" drawn with a single glyph
+        let layout = layout(vec![line(0..3, 0.0, &[(0, 30.0, false)], false)]);
+
+        let hit = hit_test("ffi", &layout, (12.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 1..2);
+        assert!(!hit.trailing);
+
+        let hit = hit_test("ffi", &layout, (16.0, 0.0)).unwrap();
+        assert_eq!(hit.cluster, 1..2);
+        assert!(hit.trailing);
+
+        let caret = caret_rect("ffi", &layout, 2, 2.0).unwrap();
+        assert_eq!((caret.x, caret.width), (19.0, 2.0));
+    }
+
+    #[test]
+    fn rtl_ligature_split_between_graphemes() {
+        // Both Hebrew letters drawn with a single glyph, the first grapheme on the right
+        let layout = layout(vec![line(0..4, 0.0, &[(0, 20.0, true)], true)]);
+
+        assert_eq!(
+            hit_test(HEBREW, &layout, (15.0, 0.0)).unwrap().cluster,
+            0..2
+        );
+        assert_eq!(hit_test(HEBREW, &layout, (5.0, 0.0)).unwrap().cluster, 2..4);
+
+        let caret = caret_rect(HEBREW, &layout, 2, 2.0).unwrap();
+        assert_eq!(caret.x, 9.0);
+    }
+
+    #[test]
+    fn caret_edges() {
+        let layout = ltr();
+        assert_eq!(caret_rect("abc", &layout, 0, 2.0).unwrap().x, -1.0);
+        assert_eq!(caret_rect("abc", &layout, 3, 2.0).unwrap().x, 29.0);
+        assert!(caret_rect("abc", &layout, 4, 2.0).is_none());
+
+        let caret = caret_rect("abc", &layout, 1, 2.0).unwrap();
+        assert_eq!((caret.y, caret.height), (-2.0, 10.0));
+
+        let layout = rtl();
+        assert_eq!(caret_rect(HEBREW, &layout, 0, 2.0).unwrap().x, 19.0);
+        assert_eq!(caret_rect(HEBREW, &layout, 4, 2.0).unwrap().x, -1.0);
+    }
+
+    #[test]
+    fn caret_on_empty_lines() {
+        let ltr = layout(vec![line(0..0, 0.0, &[], false)]);
+        assert_eq!(caret_rect("", &ltr, 0, 2.0).unwrap().x, -1.0);
+
+        // An empty right to left line starts at its right edge, the origin for an empty line
+        let rtl = layout(vec![line(0..0, 0.0, &[], true)]);
+        assert_eq!(caret_rect("", &rtl, 0, 2.0).unwrap().x, -1.0);
+        assert_eq!(hit_test("", &rtl, (5.0, 0.0)).unwrap().cluster, 0..0);
+    }
+
+    #[test]
+    fn caret_at_end_of_wrapped_line() {
+        // "ab cd" wrapped after the space
+        let text = "ab cd";
+        let first = [(0, 10.0, false), (1, 10.0, false), (2, 10.0, false)];
+        let second = [(3, 10.0, false), (4, 10.0, false)];
+        let layout = layout(vec![
+            line(0..3, 0.0, &first, false),
+            line(3..5, -20.0, &second, false),
+        ]);
+
+        // The caret after the space is at the start of the next line
+        assert_eq!(line_at(&layout, 3), Some(1));
+        let caret = caret_rect(text, &layout, 3, 2.0).unwrap();
+        assert_eq!((caret.x, caret.y), (-1.0, -22.0));
+
+        // The end of the text is on the last line
+        assert_eq!(line_at(&layout, 5), Some(1));
+        assert_eq!(caret_rect(text, &layout, 5, 2.0).unwrap().x, 19.0);
+
+        // Points between the lines go to the closest one
+        assert_eq!(hit_test(text, &layout, (4.0, -11.0)).unwrap().line, 1);
+        assert_eq!(hit_test(text, &layout, (4.0, -3.0)).unwrap().line, 0);
+    }
+
+    #[test]
+    fn selections_across_mixed_runs() {
+        // "ab " followed by two Hebrew letters, which are drawn right to left
+        let text = "ab \u{5D0}\u{5D1}";
+        let glyphs = [
+            (0, 10.0, false),
+            (1, 10.0, false),
+            (2, 10.0, false),
+            (5, 10.0, true),
+            (3, 10.0, true),
+        ];
+        let layout = layout(vec![line(0..7, 0.0, &glyphs, false)]);
+
+        // The first Hebrew letter is on the far right, apart from the rest of the selection
+        let rects = range_rects(text, &layout, 1..5);
+        assert_eq!(spans(&rects), vec![(10.0, 30.0), (40.0, 50.0)]);
+
+        let rects = range_rects(text, &layout, 0..7);
+        assert_eq!(spans(&rects), vec![(0.0, 50.0)]);
+        assert_eq!((rects[0].y, rects[0].height), (-2.0, 10.0));
+
+        assert!(range_rects(text, &layout, 2..2).is_empty());
+    }
+
+    #[test]
+    fn selections_across_lines() {
+        let text = "ab cd";
+        let first = [(0, 10.0, false), (1, 10.0, false), (2, 10.0, false)];
+        let second = [(3, 10.0, false), (4, 10.0, false)];
+        let layout = layout(vec![
+            line(0..3, 0.0, &first, false),
+            line(3..5, -20.0, &second, false),
+        ]);
+
+        let rects = range_rects(text, &layout, 1..4);
+        assert_eq!(spans(&rects), vec![(10.0, 30.0), (0.0, 10.0)]);
+        assert_eq!(rects[1].y, -22.0);
+    }
+
+    #[test]
+    fn window_to_view_centers_origin() {
+        assert_eq!(window_to_view((0, 0), (100, 50)), (-49.5, 24.5));
+        assert_eq!(window_to_view((50, 25), (100, 50)), (0.5, -0.5));
+    }
+}
@@ -0,0 +1,1216 @@
+use crate::font::FontInstance;
+use crate::shaping::ShapedGlyph;
+use crate::vertical::WritingMode;
+
+use std::ops::Range;
+
+/// An axis aligned rectangle, with y increasing upwards
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    /// Left edge
+    pub x: f32,
+    /// Bottom edge
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    /// Returns the smallest rectangle containing both rectangles
+    /// # Arguments
+    /// * `other` - The rectangle to combine with this one
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.x.min(other.x);
+        let bottom = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let top = (self.y + self.height).max(other.y + other.height);
+
+        Rect {
+            x: left,
+            y: bottom,
+            width: right - left,
+            height: top - bottom,
+        }
+    }
+
+    /// Returns true if a point is inside the rectangle
+    /// # Arguments
+    /// * `point` - The point to check
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        point.0 >= self.x
+            && point.0 < self.x + self.width
+            && point.1 >= self.y
+            && point.1 < self.y + self.height
+    }
+
+    /// Returns the rectangle turned a quarter turn clockwise about the origin
+    ///
+    /// Used to turn text laid out in rows into the columns of vertical text.
+    pub fn turned(&self) -> Rect {
+        Rect {
+            x: self.y,
+            y: -(self.x + self.width),
+            width: self.height,
+            height: self.width,
+        }
+    }
+
+    /// Returns the rectangle moved by an offset
+    /// # Arguments
+    /// * `offset` - Distance to move the rectangle
+    pub fn translated(&self, offset: (f32, f32)) -> Rect {
+        Rect {
+            x: self.x + offset.0,
+            y: self.y + offset.1,
+            ..*self
+        }
+    }
+}
+
+/// Vertical metrics of a font at its current size, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FontMetrics {
+    /// Distance from the baseline to the top of the tallest glyphs
+    pub ascender: f32,
+    /// Distance from the baseline to the bottom of the lowest glyphs, usually negative
+    pub descender: f32,
+    /// Extra space the font requests between the descender of one line and the ascender of the next
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    /// Returns the distance between consecutive baselines
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.line_gap
+    }
+
+    /// Returns metrics tall enough to hold text of both fonts on a shared baseline
+    /// # Arguments
+    /// * `other` - The metrics to combine with these
+    pub fn max(&self, other: &FontMetrics) -> FontMetrics {
+        FontMetrics {
+            ascender: self.ascender.max(other.ascender),
+            descender: self.descender.min(other.descender),
+            line_gap: self.line_gap.max(other.line_gap),
+        }
+    }
+
+    /// Returns metrics of the same height centered on the baseline
+    ///
+    /// Used for the columns of vertical text, whose glyphs are centered on the column's center
+    /// line rather than sitting on a baseline.
+    pub fn centered(&self) -> FontMetrics {
+        let half = (self.ascender - self.descender) / 2.0;
+
+        FontMetrics {
+            ascender: half,
+            descender: -half,
+            line_gap: self.line_gap,
+        }
+    }
+
+    /// Returns the metrics multiplied by a scale factor
+    /// # Arguments
+    /// * `scale` - The factor to multiply by
+    pub fn scaled(&self, scale: f32) -> FontMetrics {
+        FontMetrics {
+            ascender: self.ascender * scale,
+            descender: self.descender * scale,
+            line_gap: self.line_gap * scale,
+        }
+    }
+}
+
+/// A glyph placed in a layout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    /// The font and size the glyph is drawn with
+    pub font: FontInstance,
+    /// Index of the glyph in the face
+    pub glyph_index: u32,
+    /// Byte offset in the laid out text of the first character in this glyph's cluster
+    pub cluster: usize,
+    /// The pen position to draw the glyph at, relative to the layout origin
+    pub position: (f32, f32),
+    /// Offset the shaper applied to the glyph, already included in `position`
+    pub offset: (f32, f32),
+    /// Distance the pen moved after this glyph
+    pub advance: (f32, f32),
+    /// True if the glyph belongs to a run of right to left text
+    pub rtl: bool,
+    /// True if the glyph is drawn rotated 90 degrees clockwise, for text turned sideways in a
+    /// column of vertical text
+    pub sideways: bool,
+}
+
+/// A single line of laid out text
+///
+/// The lines of vertical text are columns, measured in their own direction: `baseline` is the
+/// horizontal position of the column's center line, `x` and `width` are measured downwards from
+/// the origin, and the ascender and descender extend to the right and left of the center line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// Byte range of the text on this line, excluding the line break
+    pub range: Range<usize>,
+    /// The glyphs on this line in visual order
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Vertical position of the baseline relative to the layout origin
+    pub baseline: f32,
+    /// Horizontal position of the left edge of the line
+    pub x: f32,
+    /// Advance width of the line, excluding trailing whitespace
+    pub width: f32,
+    /// Distance from the baseline to the top of the line
+    pub ascender: f32,
+    /// Distance from the baseline to the bottom of the line, usually negative
+    pub descender: f32,
+    /// True if the line belongs to a right to left paragraph
+    pub rtl: bool,
+    /// True if the line is a column of vertical text
+    pub vertical: bool,
+}
+
+impl Line {
+    /// Returns the area between two positions along the line and two distances across it
+    /// # Arguments
+    /// * `along` - Start and end of the area along the line, in the same units as `x`
+    /// * `across` - Bottom and top of the area relative to the baseline
+    pub fn area(&self, along: (f32, f32), across: (f32, f32)) -> Rect {
+        let rect = Rect {
+            x: along.0,
+            y: self.baseline + across.0,
+            width: along.1 - along.0,
+            height: across.1 - across.0,
+        };
+
+        if self.vertical {
+            rect.turned()
+        } else {
+            rect
+        }
+    }
+
+    /// Returns the start and end of a glyph's advance along the line, in the same units as `x`
+    /// # Arguments
+    /// * `glyph` - One of the line's glyphs
+    pub fn glyph_extent(&self, glyph: &PositionedGlyph) -> (f32, f32) {
+        let start = if self.vertical {
+            glyph.offset.1 - glyph.position.1
+        } else {
+            glyph.position.0 - glyph.offset.0
+        };
+
+        (start, start + glyph.advance.0 - glyph.advance.1)
+    }
+
+    /// Returns the position in `glyphs` of the logically first glyph of the cluster containing
+    /// the character at a byte offset, None if the character is not on the line or comes before
+    /// the line's first glyph
+    ///
+    /// A cluster covers the text up to the next glyph's cluster, so characters that were combined
+    /// into a single glyph, such as ligatures, map to that glyph. Characters that draw no glyph of
+    /// their own, such as the control characters removed while shaping, map to the glyph of the
+    /// cluster before them.
+    /// # Arguments
+    /// * `index` - Byte offset of the character in the text
+    pub fn logical_to_visual(&self, index: usize) -> Option<usize> {
+        if !self.range.contains(&index) {
+            return None;
+        }
+
+        // The glyph whose cluster is the closest at or before the character
+        let cluster = self
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.cluster)
+            .filter(|&cluster| cluster <= index)
+            .max()?;
+
+        // The glyphs of a right to left cluster are in reverse, so its first glyph is the last
+        let mut positions = (0..self.glyphs.len()).filter(|&i| self.glyphs[i].cluster == cluster);
+        let first = positions.next()?;
+        if self.glyphs[first].rtl {
+            Some(positions.next_back().unwrap_or(first))
+        } else {
+            Some(first)
+        }
+    }
+
+    /// Returns the byte offset of the first character drawn by the glyph at a position in
+    /// `glyphs`
+    /// # Arguments
+    /// * `glyph` - Position of the glyph in `glyphs`, counting from the left
+    pub fn visual_to_logical(&self, glyph: usize) -> Option<usize> {
+        self.glyphs.get(glyph).map(|glyph| glyph.cluster)
+    }
+
+    /// Returns the positions in `glyphs` sorted by the order of their characters in the text
+    ///
+    /// The glyphs of a cluster keep their logical order, which is reversed for right to left
+    /// text.
+    pub fn logical_order(&self) -> Vec<usize> {
+        let mut order = (0..self.glyphs.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            let within = if self.glyphs[a].rtl {
+                b.cmp(&a)
+            } else {
+                a.cmp(&b)
+            };
+            self.glyphs[a]
+                .cluster
+                .cmp(&self.glyphs[b].cluster)
+                .then(within)
+        });
+        order
+    }
+}
+
+/// Text broken into lines and positioned relative to an origin on the first baseline
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextLayout {
+    pub lines: Vec<Line>,
+    /// The area covered by all lines, from the first line's ascender to the last line's descender
+    pub bounds: Rect,
+}
+
+/// The area covered by a single glyph
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphBounds {
+    /// Byte offset in the text of the first character in this glyph's cluster
+    pub cluster: usize,
+    /// The font and size the glyph was drawn with, which may be a fallback font
+    pub font: FontInstance,
+    /// The glyph's advance horizontally and the line's ascender to descender vertically
+    pub logical: Rect,
+    /// The area covered by the glyph's bitmap, None for glyphs that draw nothing
+    pub ink: Option<Rect>,
+}
+
+/// The area covered by a single line
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineBounds {
+    /// Byte range of the text on this line, excluding the line break
+    pub range: Range<usize>,
+    /// The line's advance width horizontally and its ascender to descender vertically
+    pub logical: Rect,
+    /// The area covered by the bitmaps of the line's glyphs, None if nothing is drawn
+    pub ink: Option<Rect>,
+    /// The bounds of every glyph on the line in visual order
+    pub glyphs: Vec<GlyphBounds>,
+}
+
+/// The area covered by a block of text, relative to its layout origin
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextBounds {
+    /// The union of the logical bounds of every line
+    pub logical: Rect,
+    /// The area covered by the bitmaps of every glyph, None if nothing is drawn
+    pub ink: Option<Rect>,
+    /// The bounds of each line
+    pub lines: Vec<LineBounds>,
+}
+
+/// Computes the logical and ink bounds of every glyph and line of a layout
+/// # Arguments
+/// * `layout` - The laid out text
+/// * `ink` - Returns the area covered by a glyph's bitmap relative to the layout origin
+pub fn measure_layout<F>(layout: &TextLayout, mut ink: F) -> TextBounds
+where
+    F: FnMut(&PositionedGlyph) -> Option<Rect>,
+{
+    let mut bounds = TextBounds {
+        logical: layout.bounds,
+        ink: None,
+        lines: Vec::with_capacity(layout.lines.len()),
+    };
+
+    for line in layout.lines.iter() {
+        let across = (line.descender, line.ascender);
+
+        let mut line_bounds = LineBounds {
+            range: line.range.clone(),
+            logical: line.area((line.x, line.x + line.width), across),
+            ink: None,
+            glyphs: Vec::with_capacity(line.glyphs.len()),
+        };
+
+        for glyph in line.glyphs.iter() {
+            let glyph_ink = ink(glyph);
+
+            line_bounds.ink = match (line_bounds.ink, glyph_ink) {
+                (Some(line_ink), Some(glyph_ink)) => Some(line_ink.union(&glyph_ink)),
+                (line_ink, glyph_ink) => line_ink.or(glyph_ink),
+            };
+
+            line_bounds.glyphs.push(GlyphBounds {
+                cluster: glyph.cluster,
+                font: glyph.font,
+                logical: line.area(line.glyph_extent(glyph), across),
+                ink: glyph_ink,
+            });
+        }
+
+        bounds.ink = match (bounds.ink, line_bounds.ink) {
+            (Some(text_ink), Some(line_ink)) => Some(text_ink.union(&line_ink)),
+            (text_ink, line_ink) => text_ink.or(line_ink),
+        };
+
+        bounds.lines.push(line_bounds);
+    }
+
+    bounds
+}
+
+/// How lines are positioned along their direction, horizontally for horizontal text and
+/// vertically for the columns of vertical text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// Lines start at the anchor, on the left of left to right paragraphs and on the right of
+    /// right to left paragraphs, columns start at the top
+    #[default]
+    Start,
+    /// Lines end at the anchor, on the opposite side to `Start`
+    End,
+    /// Lines are centered on the anchor
+    Center,
+    /// Lines are stretched to fill the width of the layout by widening the spaces between words,
+    /// the last line of each paragraph is aligned to the start
+    Justify,
+}
+
+/// Options controlling how lines are positioned
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutOptions {
+    /// Multiplier applied to the height of each line
+    pub line_height: f32,
+    /// How lines are positioned along their direction
+    pub align: Alignment,
+    /// Width of the box lines are aligned within, starting at the origin, or its height for
+    /// vertical text. When None lines are aligned to the origin itself and can not be justified.
+    pub width: Option<f32>,
+    /// Whether lines are rows or columns
+    pub writing_mode: WritingMode,
+}
+
+/// A line of shaped text waiting to be positioned
+#[derive(Clone, Debug)]
+pub struct ShapedLine {
+    /// Byte range of the text on this line, excluding any line break
+    pub range: Range<usize>,
+    /// The shaped glyphs of the line, with clusters relative to the whole text
+    pub glyphs: Vec<ShapedGlyph>,
+    /// True if the line ends a paragraph, at a line break or the end of the text
+    pub paragraph_end: bool,
+    /// True if the line belongs to a right to left paragraph
+    pub rtl: bool,
+    /// Combined metrics of every font used on the line, centered for vertical text
+    pub metrics: FontMetrics,
+}
+
+/// Returns the byte range of every line in a piece of text, excluding the line breaks
+///
+/// Lines are separated by `\n`, a `\r` before the `\n` is treated as part of the line break.
+/// # Arguments
+/// * `text` - The text to split
+pub fn line_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            let end = if text[start..i].ends_with('\r') {
+                i - 1
+            } else {
+                i
+            };
+            ranges.push(start..end);
+            start = i + 1;
+        }
+    }
+    ranges.push(start..text.len());
+
+    ranges
+}
+
+/// Returns true if the character starting at a byte offset is whitespace
+/// # Arguments
+/// * `text` - The text containing the character
+/// * `index` - Byte offset of the character
+fn is_whitespace(text: &str, index: usize) -> bool {
+    match text[index..].chars().next() {
+        Some(c) => c.is_whitespace(),
+        None => false,
+    }
+}
+
+/// Positions shaped lines below one another and aligns them horizontally
+///
+/// Columns of vertical text are positioned to the left of one another and aligned vertically,
+/// with the origin on the center line of the first column.
+/// # Arguments
+/// * `text` - The text the lines were shaped from
+/// * `lines` - The shaped lines, in order
+/// * `options` - Spacing and alignment of the lines
+pub fn layout_lines(text: &str, lines: Vec<ShapedLine>, options: &LayoutOptions) -> TextLayout {
+    let mut layout = TextLayout::default();
+    let vertical = options.writing_mode == WritingMode::Vertical;
+
+    // Position of the bottom of the previous line, including the spacing below it
+    let mut line_bottom: Option<f32> = None;
+
+    for mut line in lines.into_iter() {
+        let metrics = line.metrics;
+
+        // Columns are laid out as rows turned a quarter turn counterclockwise, so their glyphs
+        // advance rightwards, then turned back once positioned
+        if vertical {
+            for glyph in line.glyphs.iter_mut() {
+                glyph.advance = (-glyph.advance.1, glyph.advance.0);
+                glyph.offset = (-glyph.offset.1, glyph.offset.0);
+            }
+        }
+
+        // The first baseline is at the origin, every other baseline sits the line's ascender
+        // below the previous line
+        let baseline = match line_bottom {
+            Some(bottom) => bottom - metrics.ascender,
+            None => 0.0,
+        };
+
+        // The line height multiplier adds or removes space below the line's descender
+        let height = metrics.ascender - metrics.descender;
+        let leading = metrics.line_height() * options.line_height - height;
+        line_bottom = Some(baseline + metrics.descender - leading);
+
+        // Whitespace at the end of a line is not visible, so it does not take part in alignment
+        let content_end = line.range.start + text[line.range.clone()].trim_end().len();
+        let is_content = |glyph: &ShapedGlyph| glyph.cluster < content_end;
+
+        let content_width: f32 = line
+            .glyphs
+            .iter()
+            .filter(|glyph| is_content(glyph))
+            .map(|glyph| glyph.advance.0)
+            .sum();
+
+        // Justified lines distribute the remaining width evenly between the spaces of the line
+        let mut extra_space = 0.0;
+        if let (Alignment::Justify, Some(width), false) =
+            (options.align, options.width, line.paragraph_end)
+        {
+            let spaces = line
+                .glyphs
+                .iter()
+                .filter(|glyph| is_content(glyph) && is_whitespace(text, glyph.cluster))
+                .count();
+
+            if spaces > 0 && width > content_width {
+                extra_space = (width - content_width) / spaces as f32;
+            }
+        }
+
+        let line_width = if extra_space > 0.0 {
+            options.width.unwrap_or(content_width)
+        } else {
+            content_width
+        };
+
+        // Find where the line starts, the start of right to left lines is on the right
+        let align = match (options.align, line.rtl) {
+            (Alignment::Start, true) | (Alignment::Justify, true) => Alignment::End,
+            (Alignment::End, true) => Alignment::Start,
+            (align, _) => align,
+        };
+        let x = match (align, options.width) {
+            (Alignment::Start, _) | (Alignment::Justify, _) => 0.0,
+            (Alignment::End, Some(width)) => width - line_width,
+            (Alignment::End, None) => -line_width,
+            (Alignment::Center, Some(width)) => (width - line_width) / 2.0,
+            (Alignment::Center, None) => -line_width / 2.0,
+        };
+
+        // Trailing whitespace of right to left lines is on the left, it hangs outside the line
+        // just like the trailing whitespace of left to right lines hangs off the right
+        let hanging: f32 = if line.rtl {
+            line.glyphs
+                .iter()
+                .take_while(|glyph| !is_content(glyph))
+                .map(|glyph| glyph.advance.0)
+                .sum()
+        } else {
+            0.0
+        };
+
+        // Place the glyphs along the baseline
+        let mut pen = (x - hanging, baseline);
+        let mut glyphs = Vec::with_capacity(line.glyphs.len());
+
+        for glyph in line.glyphs.iter() {
+            let mut advance = glyph.advance;
+            if extra_space > 0.0 && is_content(glyph) && is_whitespace(text, glyph.cluster) {
+                advance.0 += extra_space;
+            }
+
+            let mut position = (pen.0 + glyph.offset.0, pen.1 + glyph.offset.1);
+            let mut offset = glyph.offset;
+
+            pen.0 += advance.0;
+            pen.1 += advance.1;
+
+            if vertical {
+                position = (position.1, -position.0);
+                offset = (offset.1, -offset.0);
+                advance = (advance.1, -advance.0);
+            }
+
+            glyphs.push(PositionedGlyph {
+                font: glyph.font,
+                glyph_index: glyph.glyph_index,
+                cluster: glyph.cluster,
+                position,
+                offset,
+                advance,
+                rtl: glyph.rtl,
+                sideways: glyph.sideways,
+            });
+        }
+
+        layout.lines.push(Line {
+            range: line.range,
+            glyphs,
+            baseline,
+            x,
+            width: line_width,
+            ascender: metrics.ascender,
+            descender: metrics.descender,
+            rtl: line.rtl,
+            vertical,
+        });
+    }
+
+    // The block spans from the top of the first line to the bottom of the last
+    let top = match layout.lines.first() {
+        Some(line) => line.baseline + line.ascender,
+        None => 0.0,
+    };
+    let bottom = match layout.lines.last() {
+        Some(line) => line.baseline + line.descender,
+        None => 0.0,
+    };
+    let left = layout
+        .lines
+        .iter()
+        .map(|line| line.x)
+        .fold(f32::MAX, f32::min);
+    let right = layout
+        .lines
+        .iter()
+        .map(|line| line.x + line.width)
+        .fold(f32::MIN, f32::max);
+
+    let bounds = Rect {
+        x: left.min(right),
+        y: bottom,
+        width: (right - left).max(0.0),
+        height: top - bottom,
+    };
+    layout.bounds = if vertical { bounds.turned() } else { bounds };
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ascender 8, descender -2 and a line gap of 2, so baselines are 12 pixels apart
+    const METRICS: FontMetrics = FontMetrics {
+        ascender: 8.0,
+        descender: -2.0,
+        line_gap: 2.0,
+    };
+
+    /// Shapes a line with one 10 pixel glyph per character
+    /// # Arguments
+    /// * `text` - The text containing the line
+    /// * `range` - Byte range of the line
+    /// * `rtl` - Whether the line belongs to a right to left paragraph, its glyphs are reversed
+    fn shaped_line(text: &str, range: Range<usize>, rtl: bool) -> ShapedLine {
+        let mut glyphs = text[range.clone()]
+            .char_indices()
+            .map(|(i, _)| ShapedGlyph {
+                font: Default::default(),
+                glyph_index: 0,
+                cluster: range.start + i,
+                advance: (10.0, 0.0),
+                offset: (0.0, 0.0),
+                rtl,
+                sideways: false,
+            })
+            .collect::<Vec<_>>();
+        if rtl {
+            glyphs.reverse();
+        }
+
+        ShapedLine {
+            range,
+            glyphs,
+            paragraph_end: true,
+            rtl,
+            metrics: METRICS,
+        }
+    }
+
+    fn options(line_height: f32, align: Alignment, width: Option<f32>) -> LayoutOptions {
+        LayoutOptions {
+            line_height,
+            align,
+            width,
+            writing_mode: WritingMode::Horizontal,
+        }
+    }
+
+    /// Lays out every line of a text, each its own paragraph
+    fn layout(text: &str, options: &LayoutOptions) -> TextLayout {
+        let lines = line_ranges(text)
+            .into_iter()
+            .map(|range| shaped_line(text, range, false))
+            .collect();
+        layout_lines(text, lines, options)
+    }
+
+    fn baselines(layout: &TextLayout) -> Vec<f32> {
+        layout.lines.iter().map(|line| line.baseline).collect()
+    }
+
+    #[test]
+    fn splits_lines_at_line_breaks() {
+        assert_eq!(line_ranges("ab"), vec![0..2]);
+        assert_eq!(line_ranges("ab\ncd\nef"), vec![0..2, 3..5, 6..8]);
+    }
+
+    #[test]
+    fn carriage_return_belongs_to_line_break() {
+        assert_eq!(line_ranges("a\r\nb"), vec![0..1, 3..4]);
+
+        // A lone carriage return is part of the line
+        assert_eq!(line_ranges("a\rb"), vec![0..3]);
+    }
+
+    #[test]
+    fn trailing_line_break_starts_empty_line() {
+        assert_eq!(line_ranges("ab\n"), vec![0..2, 3..3]);
+        assert_eq!(line_ranges(""), vec![0..0]);
+    }
+
+    #[test]
+    fn single_line_baseline_is_origin() {
+        let layout = layout("ab", &options(1.0, Alignment::Start, None));
+
+        assert_eq!(baselines(&layout), vec![0.0]);
+        assert_eq!(
+            layout.bounds,
+            Rect {
+                x: 0.0,
+                y: -2.0,
+                width: 20.0,
+                height: 10.0
+            }
+        );
+    }
+
+    #[test]
+    fn lines_are_stacked_by_line_height() {
+        let options = options(1.0, Alignment::Start, None);
+
+        assert_eq!(baselines(&layout("ab\ncd", &options)), vec![0.0, -12.0]);
+        assert_eq!(
+            baselines(&layout("ab\ncd\nef", &options)),
+            vec![0.0, -12.0, -24.0]
+        );
+    }
+
+    #[test]
+    fn line_height_multiplier_scales_spacing() {
+        // 1.5 times the 12 pixel line height
+        let layout = layout("ab\ncd\nef", &options(1.5, Alignment::Start, None));
+        assert_eq!(baselines(&layout), vec![0.0, -18.0, -36.0]);
+
+        let layout = layout_lines(
+            "ab\ncd",
+            vec![
+                shaped_line("ab\ncd", 0..2, false),
+                shaped_line("ab\ncd", 3..5, false),
+            ],
+            &options(0.5, Alignment::Start, None),
+        );
+        assert_eq!(baselines(&layout), vec![0.0, -6.0]);
+    }
+
+    #[test]
+    fn block_bounds_cover_all_lines() {
+        let layout = layout("ab\ncdef\ne", &options(1.0, Alignment::Start, None));
+
+        // From the first ascender to the last descender, as wide as the widest line
+        assert_eq!(
+            layout.bounds,
+            Rect {
+                x: 0.0,
+                y: -26.0,
+                width: 40.0,
+                height: 34.0
+            }
+        );
+    }
+
+    #[test]
+    fn crlf_lines_exclude_line_break() {
+        let layout = layout("a\r\nb", &options(1.0, Alignment::Start, None));
+
+        assert_eq!(layout.lines.len(), 2);
+        assert_eq!(layout.lines[0].range, 0..1);
+        assert_eq!(layout.lines[0].width, 10.0);
+        assert_eq!(layout.lines[1].range, 3..4);
+        assert_eq!(layout.lines[1].glyphs[0].cluster, 3);
+    }
+
+    #[test]
+    fn trailing_line_break_adds_empty_line() {
+        let layout = layout("ab\n", &options(1.0, Alignment::Start, None));
+
+        assert_eq!(baselines(&layout), vec![0.0, -12.0]);
+        assert!(layout.lines[1].glyphs.is_empty());
+        assert_eq!(layout.lines[1].width, 0.0);
+        assert_eq!(layout.bounds.y, -14.0);
+        assert_eq!(layout.bounds.width, 20.0);
+    }
+
+    #[test]
+    fn empty_text_has_one_empty_line() {
+        let layout = layout("", &options(1.0, Alignment::Start, None));
+
+        assert_eq!(layout.lines.len(), 1);
+        assert_eq!(layout.lines[0].range, 0..0);
+        assert_eq!(
+            layout.bounds,
+            Rect {
+                x: 0.0,
+                y: -2.0,
+                width: 0.0,
+                height: 10.0
+            }
+        );
+    }
+
+    #[test]
+    fn glyphs_advance_along_baseline() {
+        let layout = layout("ab\ncd", &options(1.0, Alignment::Start, None));
+
+        let positions = layout.lines[1]
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(0.0, -12.0), (10.0, -12.0)]);
+    }
+
+    /// Lays out a single line and returns its left edge, width and the x position of each glyph
+    /// in visual order
+    fn align_line(
+        text: &str,
+        rtl: bool,
+        paragraph_end: bool,
+        options: &LayoutOptions,
+    ) -> (f32, f32, Vec<f32>) {
+        let line = ShapedLine {
+            paragraph_end,
+            ..shaped_line(text, 0..text.len(), rtl)
+        };
+        let layout = layout_lines(text, vec![line], options);
+
+        let line = &layout.lines[0];
+        let positions = line.glyphs.iter().map(|glyph| glyph.position.0).collect();
+        (line.x, line.width, positions)
+    }
+
+    #[test]
+    fn aligns_to_anchor_point() {
+        let start = align_line("ab", false, true, &options(1.0, Alignment::Start, None));
+        let end = align_line("ab", false, true, &options(1.0, Alignment::End, None));
+        let center = align_line("ab", false, true, &options(1.0, Alignment::Center, None));
+
+        assert_eq!(start, (0.0, 20.0, vec![0.0, 10.0]));
+        assert_eq!(end, (-20.0, 20.0, vec![-20.0, -10.0]));
+        assert_eq!(center, (-10.0, 20.0, vec![-10.0, 0.0]));
+    }
+
+    #[test]
+    fn aligns_within_box() {
+        let width = Some(100.0);
+
+        assert_eq!(
+            align_line("ab", false, true, &options(1.0, Alignment::Start, width)).0,
+            0.0
+        );
+        assert_eq!(
+            align_line("ab", false, true, &options(1.0, Alignment::End, width)).0,
+            80.0
+        );
+        assert_eq!(
+            align_line("ab", false, true, &options(1.0, Alignment::Center, width)).0,
+            40.0
+        );
+    }
+
+    #[test]
+    fn rtl_lines_start_on_the_right() {
+        let width = Some(100.0);
+
+        let start = align_line("ab", true, true, &options(1.0, Alignment::Start, width));
+        assert_eq!(start, (80.0, 20.0, vec![80.0, 90.0]));
+
+        let end = align_line("ab", true, true, &options(1.0, Alignment::End, width));
+        assert_eq!(end.0, 0.0);
+
+        let center = align_line("ab", true, true, &options(1.0, Alignment::Center, width));
+        assert_eq!(center.0, 40.0);
+
+        // Without a box the start of the line is at the anchor, so the line is left of it
+        let start = align_line("ab", true, true, &options(1.0, Alignment::Start, None));
+        assert_eq!(start.0, -20.0);
+    }
+
+    #[test]
+    fn trailing_whitespace_hangs_outside_alignment() {
+        let width = Some(100.0);
+
+        // The space of a left to right line hangs off the right end
+        let end = align_line("ab ", false, true, &options(1.0, Alignment::End, width));
+        assert_eq!(end, (80.0, 20.0, vec![80.0, 90.0, 100.0]));
+
+        // The space of a right to left line is drawn first, left of the line
+        let start = align_line("ab ", true, true, &options(1.0, Alignment::Start, width));
+        assert_eq!(start, (80.0, 20.0, vec![70.0, 80.0, 90.0]));
+    }
+
+    #[test]
+    fn justify_stretches_spaces_between_words() {
+        // 80 pixels of content leaves 20 pixels, shared by the two spaces
+        let justified = align_line(
+            "ab cd ef",
+            false,
+            false,
+            &options(1.0, Alignment::Justify, Some(100.0)),
+        );
+
+        assert_eq!(
+            justified,
+            (
+                0.0,
+                100.0,
+                vec![0.0, 10.0, 20.0, 40.0, 50.0, 60.0, 80.0, 90.0]
+            )
+        );
+    }
+
+    #[test]
+    fn justify_ignores_trailing_whitespace() {
+        // Only the space between the words is stretched, by all 50 remaining pixels
+        let justified = align_line(
+            "ab cd ",
+            false,
+            false,
+            &options(1.0, Alignment::Justify, Some(100.0)),
+        );
+
+        assert_eq!(
+            justified,
+            (0.0, 100.0, vec![0.0, 10.0, 20.0, 80.0, 90.0, 100.0])
+        );
+    }
+
+    #[test]
+    fn justify_leaves_last_line_at_start() {
+        let options = options(1.0, Alignment::Justify, Some(100.0));
+
+        let last = align_line("ab cd", false, true, &options);
+        assert_eq!(last, (0.0, 50.0, vec![0.0, 10.0, 20.0, 30.0, 40.0]));
+
+        // Right to left paragraphs end on the right
+        let last = align_line("ab cd", true, true, &options);
+        assert_eq!(last.0, 50.0);
+    }
+
+    #[test]
+    fn justify_rtl_fills_box() {
+        let justified = align_line(
+            "ab cd",
+            true,
+            false,
+            &options(1.0, Alignment::Justify, Some(100.0)),
+        );
+
+        // Glyphs are in visual order, the stretched space is in the middle
+        assert_eq!(justified, (0.0, 100.0, vec![0.0, 10.0, 20.0, 80.0, 90.0]));
+    }
+
+    #[test]
+    fn justify_needs_spaces_and_a_box() {
+        let no_spaces = align_line(
+            "abcd",
+            false,
+            false,
+            &options(1.0, Alignment::Justify, Some(100.0)),
+        );
+        assert_eq!(no_spaces, (0.0, 40.0, vec![0.0, 10.0, 20.0, 30.0]));
+
+        let no_box = align_line(
+            "ab cd",
+            false,
+            false,
+            &options(1.0, Alignment::Justify, None),
+        );
+        assert_eq!(no_box.1, 50.0);
+
+        // Lines wider than the box are not squeezed
+        let wide = align_line(
+            "ab cd",
+            false,
+            false,
+            &options(1.0, Alignment::Justify, Some(30.0)),
+        );
+        assert_eq!(wide.1, 50.0);
+    }
+
+    /// Stub ink of a glyph: a box inset by one pixel from its advance, from the baseline up to
+    /// six pixels above it, and no ink for spaces
+    fn ink(text: &str, glyph: &PositionedGlyph) -> Option<Rect> {
+        if is_whitespace(text, glyph.cluster) {
+            return None;
+        }
+
+        Some(Rect {
+            x: glyph.position.0 + 1.0,
+            y: glyph.position.1,
+            width: 8.0,
+            height: 6.0,
+        })
+    }
+
+    fn contains_rect(outer: &Rect, inner: &Rect) -> bool {
+        inner.x >= outer.x
+            && inner.y >= outer.y
+            && inner.x + inner.width <= outer.x + outer.width
+            && inner.y + inner.height <= outer.y + outer.height
+    }
+
+    #[test]
+    fn glyph_bounds_follow_advances() {
+        let text = "a b";
+        let layout = layout(text, &options(1.0, Alignment::Start, None));
+        let bounds = measure_layout(&layout, |glyph| ink(text, glyph));
+
+        let glyphs = &bounds.lines[0].glyphs;
+        assert_eq!(glyphs.len(), 3);
+        assert_eq!(
+            glyphs[2].logical,
+            Rect {
+                x: 20.0,
+                y: -2.0,
+                width: 10.0,
+                height: 10.0
+            }
+        );
+        assert_eq!(
+            glyphs[2].ink,
+            Some(Rect {
+                x: 21.0,
+                y: 0.0,
+                width: 8.0,
+                height: 6.0
+            })
+        );
+        assert_eq!(glyphs[1].cluster, 1);
+        assert_eq!(glyphs[1].ink, None);
+
+        for glyph in glyphs.iter() {
+            if let Some(ink) = glyph.ink {
+                assert!(contains_rect(&glyph.logical, &ink));
+            }
+        }
+    }
+
+    #[test]
+    fn line_bounds_combine_glyphs() {
+        let text = "ab \ncd";
+        let layout = layout(text, &options(1.0, Alignment::Start, None));
+        let bounds = measure_layout(&layout, |glyph| ink(text, glyph));
+
+        // The trailing space is outside the logical box but has no ink
+        let first = &bounds.lines[0];
+        assert_eq!(first.range, 0..3);
+        assert_eq!(
+            first.logical,
+            Rect {
+                x: 0.0,
+                y: -2.0,
+                width: 20.0,
+                height: 10.0
+            }
+        );
+        assert_eq!(
+            first.ink,
+            Some(Rect {
+                x: 1.0,
+                y: 0.0,
+                width: 18.0,
+                height: 6.0
+            })
+        );
+
+        let second = &bounds.lines[1];
+        assert_eq!(second.logical.y, -14.0);
+        assert!(contains_rect(&second.logical, &second.ink.unwrap()));
+    }
+
+    #[test]
+    fn text_bounds_combine_lines() {
+        let text = "ab\n\ncdef";
+        let layout = layout(text, &options(1.0, Alignment::Center, None));
+        let bounds = measure_layout(&layout, |glyph| ink(text, glyph));
+
+        assert_eq!(bounds.logical, layout.bounds);
+        assert_eq!(
+            bounds.ink,
+            Some(Rect {
+                x: -19.0,
+                y: -24.0,
+                width: 38.0,
+                height: 30.0
+            })
+        );
+        assert!(contains_rect(&bounds.logical, &bounds.ink.unwrap()));
+
+        // The empty line has no glyphs or ink, and does not widen the text
+        let empty = &bounds.lines[1];
+        assert!(empty.glyphs.is_empty());
+        assert_eq!(empty.ink, None);
+        assert_eq!(empty.logical.width, 0.0);
+        assert_eq!(empty.logical.y, -14.0);
+    }
+
+    #[test]
+    fn text_without_ink() {
+        let text = "  \n";
+        let layout = layout(text, &options(1.0, Alignment::Start, None));
+        let bounds = measure_layout(&layout, |glyph| ink(text, glyph));
+
+        assert_eq!(bounds.ink, None);
+        assert_eq!(bounds.lines.len(), 2);
+        assert!(bounds.lines.iter().all(|line| line.ink.is_none()));
+        assert_eq!(bounds.lines[0].glyphs.len(), 2);
+    }
+
+    /// Creates a line from glyphs in visual order, each with its cluster and direction
+    fn mapped_line(range: Range<usize>, glyphs: &[(usize, bool)]) -> Line {
+        let glyphs = glyphs
+            .iter()
+            .enumerate()
+            .map(|(i, &(cluster, rtl))| PositionedGlyph {
+                font: Default::default(),
+                glyph_index: i as u32,
+                cluster,
+                position: (i as f32 * 10.0, 0.0),
+                offset: (0.0, 0.0),
+                advance: (10.0, 0.0),
+                rtl,
+                sideways: false,
+            })
+            .collect::<Vec<_>>();
+
+        Line {
+            range,
+            width: glyphs.len() as f32 * 10.0,
+            glyphs,
+            baseline: 0.0,
+            x: 0.0,
+            ascender: 8.0,
+            descender: -2.0,
+            rtl: false,
+            vertical: false,
+        }
+    }
+
+    #[test]
+    fn maps_mixed_direction_line() {
+        // "ab " followed by two Hebrew letters of two bytes each, drawn right to left
+        let line = mapped_line(
+            0..7,
+            &[(0, false), (1, false), (2, false), (5, true), (3, true)],
+        );
+
+        let visual = (0..8)
+            .map(|index| line.logical_to_visual(index))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            visual,
+            vec![
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(4),
+                Some(4),
+                Some(3),
+                Some(3),
+                None
+            ]
+        );
+
+        let logical = (0..6)
+            .map(|glyph| line.visual_to_logical(glyph))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            logical,
+            vec![Some(0), Some(1), Some(2), Some(5), Some(3), None]
+        );
+
+        assert_eq!(line.logical_order(), vec![0, 1, 2, 4, 3]);
+    }
+
+    #[test]
+    fn maps_rtl_cluster_with_several_glyphs() {
+        // The first letter is drawn as a base and a mark, which are reversed in visual order
+        let line = mapped_line(0..4, &[(2, true), (0, true), (0, true)]);
+
+        assert_eq!(line.logical_to_visual(0), Some(2));
+        assert_eq!(line.logical_to_visual(2), Some(0));
+        assert_eq!(line.logical_order(), vec![2, 1, 0]);
+
+        // Left to right clusters start with their leftmost glyph
+        let line = mapped_line(0..2, &[(0, false), (0, false), (1, false)]);
+        assert_eq!(line.logical_to_visual(0), Some(0));
+        assert_eq!(line.logical_order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn characters_without_glyphs_map_to_previous_cluster() {
+        // The tab in "a\tb" is removed while shaping
+        let line = mapped_line(0..3, &[(0, false), (2, false)]);
+
+        assert_eq!(line.logical_to_visual(1), Some(0));
+        assert_eq!(line.logical_to_visual(2), Some(1));
+
+        // Nothing comes before the first glyph of a line
+        let line = mapped_line(0..3, &[(1, false), (2, false)]);
+        assert_eq!(line.logical_to_visual(0), None);
+    }
+
+    #[test]
+    fn maps_empty_line() {
+        let line = mapped_line(0..0, &[]);
+
+        assert_eq!(line.logical_to_visual(0), None);
+        assert_eq!(line.visual_to_logical(0), None);
+        assert!(line.logical_order().is_empty());
+    }
+}
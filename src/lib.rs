@@ -0,0 +1,50 @@
+extern crate freetype;
+extern crate gl;
+extern crate nalgebra;
+extern crate rustybuzz;
+extern crate unicode_bidi;
+extern crate unicode_linebreak;
+extern crate unicode_segmentation;
+
+pub mod atlas;
+pub mod batch;
+pub mod bidi;
+pub mod color;
+pub mod color_glyph;
+pub mod effect;
+pub mod font;
+pub mod font_db;
+#[cfg(feature = "fontconfig")]
+mod fontconfig;
+pub mod fonts;
+pub mod gl_util;
+pub mod glyph_cache;
+pub mod hit_test;
+pub mod layout;
+pub mod outline;
+pub mod packer;
+pub mod post_process;
+pub mod renderer;
+pub mod sdf;
+pub mod shader;
+pub mod shaping;
+pub mod span;
+pub mod text_input;
+pub mod variation;
+pub mod vertical;
+pub mod wrap;
+
+pub use bidi::Direction;
+pub use color::Color;
+pub use effect::{Glow, Shadow};
+pub use font::{FontData, FontId, Slant, Stretch, Weight};
+pub use font_db::{FaceInfo, FontDatabase, FontQuery};
+pub use glyph_cache::{GlyphMode, SubpixelLayout};
+pub use hit_test::Hit;
+pub use layout::{Alignment, GlyphBounds, LineBounds, Rect, TextBounds};
+pub use outline::{LineJoin, Outline};
+pub use renderer::{TextRenderer, TextStyle};
+pub use span::{Decoration, TextSpan};
+pub use text_input::TextInput;
+pub use variation::{NamedInstance, Variation, VariationAxis};
+pub use vertical::WritingMode;
@@ -0,0 +1,42 @@
+use crate::color::Color;
+
+/// How the corners of an outline are shaped where the glyph's edges meet at an angle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LineJoin {
+    /// Corners are rounded off
+    #[default]
+    Round,
+    /// Corners are cut off straight
+    Bevel,
+    /// Corners are extended to a point, very sharp corners are cut off
+    Miter,
+}
+
+/// A stroke drawn around the edges of glyphs, behind the glyphs themselves
+///
+/// Glyphs drawn as distance fields are always outlined with round corners, as the outline is
+/// derived from the distance to the glyph's edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    /// Distance the outline extends past the glyph's edge, in pixels before `TextStyle::scale`
+    /// is applied
+    pub width: f32,
+    /// Color of the outline, its alpha fades the outline
+    pub color: Color,
+    /// How corners are shaped
+    pub join: LineJoin,
+}
+
+impl Outline {
+    /// Creates an outline with rounded corners
+    /// # Arguments
+    /// * `width` - Distance the outline extends past the glyph's edge, in pixels
+    /// * `color` - Color of the outline
+    pub fn new(width: f32, color: Color) -> Outline {
+        Outline {
+            width,
+            color,
+            join: LineJoin::Round,
+        }
+    }
+}
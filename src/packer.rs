@@ -0,0 +1,283 @@
+/// A rectangle allocated from a packer, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A horizontal strip of the packing area that rectangles are placed along
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+}
+
+/// Packs rectangles into a fixed size area using the shelf algorithm
+///
+/// Rectangles are placed left to right along shelves, a new shelf is opened below the last one
+/// when no existing shelf has room. The packer only tracks space, so it does not need a GPU.
+#[derive(Clone, Debug)]
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    /// Creates an empty packer
+    /// # Arguments
+    /// * `width` - Width of the packing area
+    /// * `height` - Height of the packing area
+    /// * `padding` - Empty space to leave around every rectangle
+    pub fn new(width: u32, height: u32, padding: u32) -> ShelfPacker {
+        ShelfPacker {
+            width,
+            height,
+            padding,
+            shelves: vec![],
+        }
+    }
+
+    /// Returns the dimensions of the packing area
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Returns true if a rectangle of this size could be packed into an empty packer
+    /// # Arguments
+    /// * `width` - Width of the rectangle
+    /// * `height` - Height of the rectangle
+    pub fn fits_empty(&self, width: u32, height: u32) -> bool {
+        width + self.padding * 2 <= self.width && height + self.padding * 2 <= self.height
+    }
+
+    /// Allocates space for a rectangle, returning its position or None if the area is full
+    ///
+    /// The returned rectangle excludes the padding, which is reserved on every side.
+    /// # Arguments
+    /// * `width` - Width of the rectangle
+    /// * `height` - Height of the rectangle
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<PackedRect> {
+        let padded_width = width + self.padding * 2;
+        let padded_height = height + self.padding * 2;
+
+        if padded_width > self.width || padded_height > self.height {
+            return None;
+        }
+
+        // Find the shelf that fits the rectangle while wasting the least height
+        let mut best: Option<usize> = None;
+        for (i, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height < padded_height || shelf.cursor + padded_width > self.width {
+                continue;
+            }
+
+            match best {
+                Some(b) if self.shelves[b].height <= shelf.height => (),
+                _ => best = Some(i),
+            }
+        }
+
+        // Only reuse a shelf if it is not much taller than the rectangle, otherwise start a new
+        // shelf when there is room for one
+        let next_y = match self.shelves.last() {
+            Some(shelf) => shelf.y + shelf.height,
+            None => 0,
+        };
+        let room_for_shelf = next_y + padded_height <= self.height;
+
+        let index = match best {
+            Some(b) if !room_for_shelf || self.shelves[b].height <= padded_height * 3 / 2 => b,
+            _ if room_for_shelf => {
+                self.shelves.push(Shelf {
+                    y: next_y,
+                    height: padded_height,
+                    cursor: 0,
+                });
+                self.shelves.len() - 1
+            }
+            _ => return None,
+        };
+
+        let shelf = &mut self.shelves[index];
+        let rect = PackedRect {
+            x: shelf.cursor + self.padding,
+            y: shelf.y + self.padding,
+            width,
+            height,
+        };
+        shelf.cursor += padded_width;
+
+        Some(rect)
+    }
+
+    /// Frees all allocated space
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_shelf_with_room() {
+        let mut packer = ShelfPacker::new(64, 64, 0);
+
+        let first = packer.pack(10, 10).unwrap();
+        let second = packer.pack(20, 8).unwrap();
+
+        assert_eq!(
+            first,
+            PackedRect {
+                x: 0,
+                y: 0,
+                width: 10,
+                height: 10
+            }
+        );
+        assert_eq!(
+            second,
+            PackedRect {
+                x: 10,
+                y: 0,
+                width: 20,
+                height: 8
+            }
+        );
+    }
+
+    #[test]
+    fn opens_shelf_when_row_is_full() {
+        let mut packer = ShelfPacker::new(32, 64, 0);
+
+        packer.pack(20, 10).unwrap();
+        let second = packer.pack(20, 10).unwrap();
+
+        assert_eq!(
+            second,
+            PackedRect {
+                x: 0,
+                y: 10,
+                width: 20,
+                height: 10
+            }
+        );
+    }
+
+    #[test]
+    fn opens_shelf_for_much_shorter_rect() {
+        let mut packer = ShelfPacker::new(64, 64, 0);
+
+        packer.pack(10, 30).unwrap();
+        let short = packer.pack(10, 5).unwrap();
+
+        // A 30 pixel shelf wastes too much height on a 5 pixel rectangle
+        assert_eq!(short.y, 30);
+    }
+
+    #[test]
+    fn opens_shelf_for_taller_rect() {
+        let mut packer = ShelfPacker::new(64, 64, 0);
+
+        packer.pack(10, 10).unwrap();
+        let tall = packer.pack(10, 20).unwrap();
+
+        assert_eq!(
+            tall,
+            PackedRect {
+                x: 0,
+                y: 10,
+                width: 10,
+                height: 20
+            }
+        );
+    }
+
+    #[test]
+    fn padding_offsets_rects() {
+        let mut packer = ShelfPacker::new(64, 64, 2);
+
+        let first = packer.pack(10, 10).unwrap();
+        let second = packer.pack(10, 10).unwrap();
+        let third = packer.pack(60, 10).unwrap();
+
+        assert_eq!(
+            first,
+            PackedRect {
+                x: 2,
+                y: 2,
+                width: 10,
+                height: 10
+            }
+        );
+        assert_eq!(
+            second,
+            PackedRect {
+                x: 16,
+                y: 2,
+                width: 10,
+                height: 10
+            }
+        );
+        assert_eq!(
+            third,
+            PackedRect {
+                x: 2,
+                y: 16,
+                width: 60,
+                height: 10
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_rect_bigger_than_area() {
+        let mut packer = ShelfPacker::new(32, 32, 1);
+
+        assert!(!packer.fits_empty(31, 10));
+        assert!(packer.pack(31, 10).is_none());
+        assert!(packer.pack(10, 33).is_none());
+
+        assert!(packer.fits_empty(30, 30));
+        assert!(packer.pack(30, 30).is_some());
+    }
+
+    #[test]
+    fn returns_none_when_full() {
+        let mut packer = ShelfPacker::new(20, 20, 0);
+
+        for _ in 0..4 {
+            assert!(packer.pack(10, 10).is_some());
+        }
+
+        assert!(packer.pack(10, 10).is_none());
+        assert!(packer.pack(1, 1).is_none());
+    }
+
+    #[test]
+    fn clear_frees_all_space() {
+        let mut packer = ShelfPacker::new(20, 20, 0);
+
+        packer.pack(20, 20).unwrap();
+        assert!(packer.pack(1, 1).is_none());
+
+        packer.clear();
+
+        assert_eq!(packer.size(), (20, 20));
+        assert_eq!(
+            packer.pack(20, 20),
+            Some(PackedRect {
+                x: 0,
+                y: 0,
+                width: 20,
+                height: 20
+            })
+        );
+    }
+}
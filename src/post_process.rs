@@ -0,0 +1,277 @@
+use gl::types::GLuint;
+
+use crate::effect::{Blend, Effect};
+use crate::gl_util;
+use crate::shader::{Program, Shader};
+
+use std::ffi::c_void;
+
+/// Shader sources, built into the library so it does not depend on the working directory
+const POST_VERTEX_SHADER: &str = include_str!("shaders/post_vertex.glsl");
+const BLUR_FRAGMENT_SHADER: &str = include_str!("shaders/blur_fragment.glsl");
+const COMPOSITE_FRAGMENT_SHADER: &str = include_str!("shaders/composite_fragment.glsl");
+
+/// A quad covering the whole viewport as two triangles, with positions in normalized device
+/// coordinates followed by texture coordinates
+const SCREEN_QUAD: [f32; 24] = [
+    -1.0, 1.0, 0.0, 1.0, //
+    -1.0, -1.0, 0.0, 0.0, //
+    1.0, -1.0, 1.0, 0.0, //
+    -1.0, 1.0, 0.0, 1.0, //
+    1.0, -1.0, 1.0, 0.0, //
+    1.0, 1.0, 1.0, 1.0, //
+];
+
+/// A texture that can be drawn into through a framebuffer
+struct RenderTarget {
+    framebuffer: GLuint,
+    texture: GLuint,
+}
+
+impl RenderTarget {
+    /// Creates a render target without any storage
+    fn new() -> RenderTarget {
+        RenderTarget {
+            framebuffer: gl_util::generate_framebuffer(),
+            texture: gl_util::generate_texture(),
+        }
+    }
+
+    /// Reallocates the target's texture at a new size, discarding its contents
+    /// # Arguments
+    /// * `size` - Width and height of the target in pixels
+    fn resize(&self, size: (u32, u32)) -> Result<(), String> {
+        gl_util::allocate_render_texture(self.texture, size.0, size.1);
+        gl_util::attach_texture(self.framebuffer, self.texture)
+    }
+}
+
+impl Drop for RenderTarget {
+    /// Deletes the framebuffer and its texture from the GPU
+    fn drop(&mut self) {
+        gl_util::delete_framebuffer(self.framebuffer);
+        gl_util::delete_texture(self.texture);
+    }
+}
+
+/// Blurs text drawn into an offscreen framebuffer and composites it onto the screen, used to draw
+/// shadows and glows
+///
+/// The blur is a Gaussian applied in two passes, first horizontally into a second framebuffer
+/// and then vertically back into the first, which takes far fewer samples per pixel than a
+/// single two dimensional pass. The offscreen framebuffers match the size of the viewport.
+pub struct PostProcess {
+    targets: [RenderTarget; 2],
+    size: (u32, u32),
+    vao: GLuint,
+    vbo: GLuint,
+    blur_program: Program,
+    composite_program: Program,
+    /// Locations of uniforms set for every blur and composite, found once when the programs
+    /// are created
+    sigma_id: i32,
+    direction_id: i32,
+    color_id: i32,
+    /// State of the framebuffer being drawn to before `begin`, restored by `end`
+    previous_framebuffer: u32,
+    previous_viewport: [i32; 4],
+    previous_blend: [u32; 4],
+    previous_blending: bool,
+    previous_scissor: Option<[i32; 4]>,
+}
+
+impl PostProcess {
+    /// Creates the offscreen framebuffers and the shaders used to blur and composite them
+    pub fn new() -> Result<PostProcess, String> {
+        let blur_program = create_program(BLUR_FRAGMENT_SHADER)?;
+        let composite_program = create_program(COMPOSITE_FRAGMENT_SHADER)?;
+        let sigma_id = gl_util::uniform_location("sigma", blur_program.id);
+        let direction_id = gl_util::uniform_location("direction", blur_program.id);
+        let color_id = gl_util::uniform_location("color", composite_program.id);
+
+        let vao = gl_util::generate_vertex_array();
+        let vbo = gl_util::generate_buffer();
+        gl_util::set_buffer_data(vbo, &SCREEN_QUAD);
+
+        gl_util::bind_array(vao);
+        gl_util::bind_buffer(vbo);
+        unsafe {
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                0,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                (4 * std::mem::size_of::<f32>()) as i32,
+                std::ptr::null::<c_void>(),
+            );
+        }
+        gl_util::bind_array(0);
+        gl_util::bind_buffer(0);
+
+        Ok(PostProcess {
+            targets: [RenderTarget::new(), RenderTarget::new()],
+            size: (0, 0),
+            vao,
+            vbo,
+            blur_program,
+            composite_program,
+            sigma_id,
+            direction_id,
+            color_id,
+            previous_framebuffer: 0,
+            previous_viewport: [0; 4],
+            previous_blend: [0; 4],
+            previous_blending: false,
+            previous_scissor: None,
+        })
+    }
+
+    /// Redirects drawing into a cleared offscreen framebuffer the size of the viewport
+    ///
+    /// Drawn fragments are blended so the framebuffer accumulates their coverage in its alpha
+    /// channel. The previous framebuffer, viewport, blend state and scissor box are restored by
+    /// `end`, the scissor box only applies when compositing.
+    pub fn begin(&mut self) -> Result<(), String> {
+        self.previous_framebuffer = gl_util::framebuffer_binding();
+        self.previous_viewport = gl_util::viewport();
+        self.previous_blend = gl_util::blend_function();
+        self.previous_blending = gl_util::blending_enabled();
+        self.previous_scissor = gl_util::scissor();
+
+        // The whole offscreen framebuffer is cleared and blurred
+        gl_util::set_scissor(None);
+
+        // Follow the size of the viewport
+        let size = (
+            self.previous_viewport[2].max(1) as u32,
+            self.previous_viewport[3].max(1) as u32,
+        );
+        if size != self.size {
+            for target in self.targets.iter() {
+                if let Err(message) = target.resize(size) {
+                    gl_util::bind_framebuffer(self.previous_framebuffer);
+                    gl_util::set_scissor(self.previous_scissor);
+                    return Err(message);
+                }
+            }
+            self.size = size;
+        }
+
+        gl_util::bind_framebuffer(self.targets[0].framebuffer);
+        gl_util::set_viewport([0, 0, size.0 as i32, size.1 as i32]);
+        clear();
+
+        gl_util::set_blending(true);
+        gl_util::set_blend_function([
+            gl::SRC_ALPHA,
+            gl::ONE_MINUS_SRC_ALPHA,
+            gl::ONE,
+            gl::ONE_MINUS_SRC_ALPHA,
+        ]);
+
+        Ok(())
+    }
+
+    /// Blurs everything drawn since `begin` and draws it onto the previous framebuffer in the
+    /// effect's color
+    /// # Arguments
+    /// * `effect` - The blur, color and blending of the effect
+    pub fn end(&mut self, effect: &Effect) {
+        gl_util::bind_array(self.vao);
+
+        // A blur radius covers about two standard deviations of the Gaussian, like CSS blurs
+        let sigma = effect.blur / 2.0;
+        if sigma > 0.1 {
+            gl_util::set_blending(false);
+            self.blur_program.set_used();
+            gl_util::set_uniform_float_at(self.sigma_id, sigma);
+
+            let texel = (1.0 / self.size.0 as f32, 1.0 / self.size.1 as f32);
+            self.blur_pass(0, 1, (texel.0, 0.0));
+            self.blur_pass(1, 0, (0.0, texel.1));
+        }
+
+        // Composite the blurred coverage onto the previous framebuffer
+        gl_util::bind_framebuffer(self.previous_framebuffer);
+        gl_util::set_viewport(self.previous_viewport);
+        gl_util::set_scissor(self.previous_scissor);
+        gl_util::set_blending(true);
+        match effect.blend {
+            Blend::Over => gl_util::set_blend_function([
+                gl::SRC_ALPHA,
+                gl::ONE_MINUS_SRC_ALPHA,
+                gl::ONE,
+                gl::ONE_MINUS_SRC_ALPHA,
+            ]),
+            Blend::Add => gl_util::set_blend_function([gl::SRC_ALPHA, gl::ONE, gl::ZERO, gl::ONE]),
+        }
+
+        let color = effect.color;
+        self.composite_program.set_used();
+        gl_util::set_uniform_float_vec4_at(self.color_id, &[color.r, color.g, color.b, color.a]);
+        gl_util::bind_texture(self.targets[0].texture);
+        gl_util::draw_triangles(6);
+
+        gl_util::bind_array(0);
+        gl_util::bind_texture(0);
+        gl_util::set_blend_function(self.previous_blend);
+        gl_util::set_blending(self.previous_blending);
+    }
+
+    /// Blurs one offscreen framebuffer along a single axis into the other
+    /// # Arguments
+    /// * `source` - Index of the target to read from
+    /// * `destination` - Index of the target to draw into
+    /// * `direction` - Distance between samples in texture coordinates
+    fn blur_pass(&self, source: usize, destination: usize, direction: (f32, f32)) {
+        gl_util::bind_framebuffer(self.targets[destination].framebuffer);
+        gl_util::set_uniform_float_vec2_at(self.direction_id, &[direction.0, direction.1]);
+        gl_util::bind_texture(self.targets[source].texture);
+        gl_util::draw_triangles(6);
+    }
+}
+
+impl Drop for PostProcess {
+    /// Deletes the vertex buffers from the GPU
+    fn drop(&mut self) {
+        gl_util::delete_buffer(self.vbo);
+        gl_util::delete_vertex_array(self.vao);
+    }
+}
+
+/// Clears the bound framebuffer to transparent, keeping the clear color set by the application
+fn clear() {
+    let mut previous = [0.0f32; 4];
+
+    unsafe {
+        gl::GetFloatv(gl::COLOR_CLEAR_VALUE, previous.as_mut_ptr());
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::ClearColor(previous[0], previous[1], previous[2], previous[3]);
+    }
+}
+
+/// Creates a shader program drawing the screen quad
+/// # Arguments
+/// * `fragment_source` - Source of the fragment shader
+fn create_program(fragment_source: &str) -> Result<Program, String> {
+    let vertex_shader = match Shader::new_from_source(POST_VERTEX_SHADER, gl::VERTEX_SHADER) {
+        Ok(shader) => shader,
+        Err(message) => return Err(format!("Failed to create vertex shader: {}", message)),
+    };
+
+    let fragment_shader = match Shader::new_from_source(fragment_source, gl::FRAGMENT_SHADER) {
+        Ok(shader) => shader,
+        Err(message) => return Err(format!("Failed to create fragment shader: {}", message)),
+    };
+
+    match Program::new()
+        .attach_shader(&vertex_shader)
+        .attach_shader(&fragment_shader)
+        .link()
+    {
+        Ok(program) => Ok(program),
+        Err(message) => Err(format!("Failed to create shader program: {}", message)),
+    }
+}
@@ -0,0 +1,1298 @@
+use nalgebra::Orthographic3;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::atlas::{AtlasRegion, GlyphAtlas};
+use crate::batch::QuadBatch;
+use crate::bidi::{self, Direction, Paragraph};
+use crate::color::Color;
+use crate::effect::{Effect, Glow, Shadow};
+use crate::font::{self, Font, FontData, FontId, FontInstance, Slant, Weight};
+use crate::font_db::FaceInfo;
+use crate::gl_util;
+use crate::glyph_cache::{Character, GlyphCache, GlyphMode};
+use crate::hit_test::{self, Hit};
+use crate::layout::{
+    self, Alignment, FontMetrics, LayoutOptions, Line, PositionedGlyph, Rect, ShapedLine,
+    TextBounds, TextLayout,
+};
+use crate::outline::Outline;
+use crate::post_process::PostProcess;
+use crate::shader::{Program, Shader};
+use crate::shaping::{self, RunDirection, ShapedGlyph};
+use crate::span::{self, Attributes, Decoration, TextSpan};
+use crate::variation::Variation;
+use crate::vertical::{self, WritingMode};
+use crate::wrap;
+
+use std::ffi::CString;
+use std::ops::Range;
+use std::path::Path;
+
+/// Width and height of each glyph atlas page
+const ATLAS_PAGE_SIZE: u32 = 1024;
+
+/// Empty space left around each glyph in the atlas
+const ATLAS_PADDING: u32 = 1;
+
+/// Batch layers text is drawn in, outlines are drawn behind all glyphs so they never cover
+/// neighbouring glyphs, and rectangles such as selection highlights behind everything
+const BACKGROUND_LAYER: usize = 0;
+const OUTLINE_LAYER: usize = 1;
+const TEXT_LAYER: usize = 2;
+
+/// Shader sources, built into the library so it does not depend on the working directory
+const VERTEX_SHADER: &str = include_str!("shaders/vertex.glsl");
+const FRAGMENT_SHADER: &str = include_str!("shaders/fragment.glsl");
+const SDF_FRAGMENT_SHADER: &str = include_str!("shaders/sdf_fragment.glsl");
+const LCD_FRAGMENT_SHADER: &str = include_str!("shaders/lcd_fragment.glsl");
+const LCD_CHANNEL_FRAGMENT_SHADER: &str = include_str!("shaders/lcd_channel_fragment.glsl");
+
+/// Options controlling how a piece of text is drawn
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    /// Multiplier applied to the rasterized glyph size
+    pub scale: f32,
+    /// Whether to adjust the spacing of glyph pairs using the font's kerning data
+    pub kerning: bool,
+    /// Multiplier applied to the font's line height when spacing lines
+    pub line_height: f32,
+    /// Width to wrap lines at, or the height to wrap the columns of vertical text at. Lines are
+    /// only broken at line breaks when None.
+    pub max_width: Option<f32>,
+    /// How lines are positioned along their direction
+    pub align: Alignment,
+    /// Whether text is written in horizontal lines or vertical columns
+    pub writing_mode: WritingMode,
+    /// The base direction of each paragraph, mixed left to right and right to left text within a
+    /// paragraph is reordered following the Unicode Bidirectional Algorithm
+    pub direction: Direction,
+    /// Name of the font family, the renderer's font is used when None
+    pub family: Option<String>,
+    /// The font size in points, the renderer's font size is used when None
+    pub size: Option<f32>,
+    pub weight: Weight,
+    pub slant: Slant,
+    /// Color of the text, its alpha fades the text
+    pub color: Color,
+    /// Lines drawn under or through the text
+    pub decoration: Decoration,
+    /// Stroke drawn around the glyphs and lines of the text
+    pub outline: Option<Outline>,
+    /// Blurred copy of the text drawn behind it
+    pub shadow: Option<Shadow>,
+    /// Blurred halo of light drawn around the text
+    pub glow: Option<Glow>,
+    /// Axis values of variable fonts, like the CSS `font-variation-settings`
+    ///
+    /// The weight and optical size axes follow the style's weight and font size unless they are
+    /// set here.
+    pub variations: Vec<Variation>,
+    /// Families to draw characters missing from the style's font with, in order of preference
+    pub fallback: Vec<String>,
+    /// Attributes applied to parts of the text in place of the style's, later spans take priority
+    pub spans: Vec<TextSpan>,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            scale: 1.0,
+            kerning: true,
+            line_height: 1.0,
+            max_width: None,
+            align: Alignment::Start,
+            writing_mode: WritingMode::Horizontal,
+            direction: Direction::Auto,
+            family: None,
+            size: None,
+            weight: Weight::NORMAL,
+            slant: Slant::Normal,
+            color: Color::WHITE,
+            decoration: Decoration::default(),
+            outline: None,
+            shadow: None,
+            glow: None,
+            variations: vec![],
+            fallback: vec![],
+            spans: vec![],
+        }
+    }
+}
+
+/// How drawn glyphs are combined with the contents of the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compositing {
+    /// Blend using the single coverage value of each pixel as alpha
+    Alpha,
+    /// Blend each color channel by its own coverage in a single pass using a second shader output
+    DualSource,
+    /// Blend each color channel by its own coverage, drawing one channel at a time
+    PerChannel,
+}
+
+/// Renders text using OpenGL
+///
+/// The renderer starts with a single font, more fonts can be added and selected by family,
+/// weight and slant through the text style or its spans.
+///
+/// A valid OpenGL context must be current when the renderer is created, used and dropped.
+pub struct TextRenderer {
+    library: freetype::Library,
+    stroker: freetype::Stroker,
+    glyphs: GlyphCache,
+    font: FontId,
+    font_size: f32,
+    batch: QuadBatch,
+    program: Program,
+    projection_id: i32,
+    /// Locations of uniforms set for every draw, found once when the program is created
+    color_glyphs_id: i32,
+    channel_id: i32,
+    compositing: Compositing,
+    /// Quads of text with shadows or glows, drawn blurred before the text
+    effects: Vec<(Effect, QuadBatch)>,
+    /// Draws the coverage of text into the offscreen framebuffer blurred by `post`
+    effect_program: Program,
+    effect_color_glyphs_id: i32,
+    effect_projection_id: i32,
+    post: PostProcess,
+}
+
+impl TextRenderer {
+    /// Creates a new text renderer drawing antialiased coverage bitmaps
+    /// # Arguments
+    /// * `font_path` - Path to the font file to render with
+    /// * `font_size` - The font size in points
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    pub fn new(
+        font_path: impl AsRef<Path>,
+        font_size: u32,
+        dpi: (u32, u32),
+    ) -> Result<TextRenderer, String> {
+        TextRenderer::with_mode(font_path, font_size, dpi, GlyphMode::Coverage)
+    }
+
+    /// Creates a new text renderer with a choice of how glyphs are rasterized
+    ///
+    /// With `GlyphMode::DistanceField` glyphs are rasterized once at `font_size` and stay crisp
+    /// at any `TextStyle::scale`. With `GlyphMode::Subpixel` glyphs are antialiased per color
+    /// subpixel, which is sharpest on low resolution LCD panels when drawn unscaled.
+    /// # Arguments
+    /// * `font_path` - Path to the font file to render with
+    /// * `font_size` - The font size in points
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    /// * `mode` - How glyph bitmaps are generated
+    pub fn with_mode(
+        font_path: impl AsRef<Path>,
+        font_size: u32,
+        dpi: (u32, u32),
+        mode: GlyphMode,
+    ) -> Result<TextRenderer, String> {
+        TextRenderer::from_memory(font::read_font(font_path.as_ref())?, font_size, dpi, mode)
+    }
+
+    /// Creates a new text renderer from the contents of a font file
+    ///
+    /// Fonts embedded in the program, such as those in the `fonts` module, are used without
+    /// being copied.
+    /// # Arguments
+    /// * `data` - The contents of the font file to render with
+    /// * `font_size` - The font size in points
+    /// * `dpi` - The horizontal and vertical resolution of the display
+    /// * `mode` - How glyph bitmaps are generated
+    pub fn from_memory(
+        data: impl Into<FontData>,
+        font_size: u32,
+        dpi: (u32, u32),
+        mode: GlyphMode,
+    ) -> Result<TextRenderer, String> {
+        // Initialize Freetype
+        let ft_library = match freetype::Library::init() {
+            Ok(library) => library,
+            Err(message) => return Err(format!("Failed to initialize Freetype: {}", message)),
+        };
+
+        // Subpixel rendering needs a filter to reduce color fringes, Freetype falls back to its own
+        // subpixel rendering if filtering is not available
+        if let GlyphMode::Subpixel(_) = mode {
+            let _ = ft_library.set_lcd_filter(freetype::LcdFilter::LcdFilterDefault);
+        }
+
+        let stroker = match ft_library.new_stroker() {
+            Ok(stroker) => stroker,
+            Err(message) => return Err(format!("Failed to create glyph stroker: {}", message)),
+        };
+
+        // Glyphs of every font are rasterized into a shared atlas as they are first used
+        let atlas = GlyphAtlas::new(ATLAS_PAGE_SIZE, ATLAS_PADDING);
+        let mut glyphs = GlyphCache::new(atlas, mode, dpi);
+        let font = glyphs.add_font(Font::new(&ft_library, data, 0)?);
+
+        let compositing = match mode {
+            GlyphMode::Coverage | GlyphMode::DistanceField => Compositing::Alpha,
+            GlyphMode::Subpixel(_) if gl_util::supports_dual_source_blending() => {
+                Compositing::DualSource
+            }
+            GlyphMode::Subpixel(_) => Compositing::PerChannel,
+        };
+        let program = create_program(mode, compositing)?;
+        let effect_program = create_program(mode, Compositing::Alpha)?;
+
+        // Find the projection uniform
+        let projection_id = unsafe {
+            gl::GetUniformLocation(program.id, CString::new("projection").unwrap().as_ptr())
+        };
+        let color_glyphs_id = gl_util::uniform_location("color_glyphs", program.id);
+        let channel_id = gl_util::uniform_location("channel", program.id);
+        let effect_color_glyphs_id = gl_util::uniform_location("color_glyphs", effect_program.id);
+        let effect_projection_id = gl_util::uniform_location("projection", effect_program.id);
+
+        Ok(TextRenderer {
+            library: ft_library,
+            stroker,
+            glyphs,
+            font,
+            font_size: font_size as f32,
+            batch: QuadBatch::new(),
+            program,
+            projection_id,
+            color_glyphs_id,
+            channel_id,
+            compositing,
+            effects: vec![],
+            effect_program,
+            effect_color_glyphs_id,
+            effect_projection_id,
+            post: PostProcess::new()?,
+        })
+    }
+
+    /// Returns the font face used by this renderer when the style does not name a family
+    pub fn face(&self) -> &freetype::Face {
+        self.glyphs.font(self.font).face()
+    }
+
+    /// Loads another font that text can be drawn with
+    ///
+    /// The font is selected through its family name, weight and slant, as read from the font file.
+    /// # Arguments
+    /// * `font_path` - Path to the font file
+    pub fn add_font(&mut self, font_path: impl AsRef<Path>) -> Result<FontId, String> {
+        self.add_font_data(font::read_font(font_path.as_ref())?)
+    }
+
+    /// Loads another font that text can be drawn with from the contents of a font file
+    /// # Arguments
+    /// * `data` - The contents of the font file
+    pub fn add_font_data(&mut self, data: impl Into<FontData>) -> Result<FontId, String> {
+        let font = Font::new(&self.library, data, 0)?;
+        Ok(self.glyphs.add_font(font))
+    }
+
+    /// Loads a face found in a font database, such as the result of `FontDatabase::query`
+    /// # Arguments
+    /// * `face` - The face to load
+    pub fn add_face(&mut self, face: &FaceInfo) -> Result<FontId, String> {
+        let font = Font::new(&self.library, font::read_font(&face.path)?, face.index)?;
+        Ok(self.glyphs.add_font(font))
+    }
+
+    /// Returns a loaded font
+    /// # Arguments
+    /// * `font` - ID of the font
+    pub fn font(&self, font: FontId) -> &Font {
+        self.glyphs.font(font)
+    }
+
+    /// Calculates a projection matrix based on the viewport dimensions and updates the GPU with it
+    /// # Arguments
+    /// * `width` - Width of the viewport in pixels
+    /// * `height` - Height of the viewport in pixels
+    pub fn set_projection(&self, width: u32, height: u32) {
+        let w = width as f32 / 2.0;
+        let h = height as f32 / 2.0;
+
+        let projection = Orthographic3::new(-w, w, -h, h, -1.0, 1.0);
+
+        // Write the projection to the GPU
+        self.program.set_used();
+        unsafe {
+            gl::UniformMatrix4fv(
+                self.projection_id,
+                1,
+                gl::FALSE,
+                projection.to_homogeneous().as_slice().as_ptr(),
+            );
+        };
+
+        // Effects are drawn offscreen at the same size as the viewport
+        self.effect_program.set_used();
+        unsafe {
+            gl::UniformMatrix4fv(
+                self.effect_projection_id,
+                1,
+                gl::FALSE,
+                projection.to_homogeneous().as_slice().as_ptr(),
+            );
+        };
+    }
+
+    /// Shapes a single line of text into positioned glyphs
+    ///
+    /// Each glyph records the byte offset of the cluster it was produced from, which maps the
+    /// glyphs back to the text for hit testing.
+    /// # Arguments
+    /// * `text` - The text to shape
+    /// * `style` - The style the text will be drawn with
+    pub fn shape(&mut self, text: &str, style: &TextStyle) -> Vec<ShapedGlyph> {
+        let paragraph = Paragraph::new(text, 0..text.len(), style.direction);
+        self.shape_range(text, 0..text.len(), style, &paragraph)
+    }
+
+    /// Breaks text into lines and positions its glyphs, aligned to a point
+    ///
+    /// Text is broken at line breaks, and wrapped to the style's maximum width if it has one.
+    /// The byte range of every resulting line is reported in the layout. The layout origin is
+    /// on the baseline of the first line, lines are aligned horizontally to it. Vertical text is
+    /// aligned vertically to an origin on the center line of its first column.
+    /// # Arguments
+    /// * `text` - The text to lay out
+    /// * `style` - The style the text will be drawn with
+    pub fn layout(&mut self, text: &str, style: &TextStyle) -> TextLayout {
+        self.layout_with_width(text, style, None)
+    }
+
+    /// Breaks text into lines and positions its glyphs, aligned within a box
+    ///
+    /// Lines are wrapped to the width of the box. The layout origin is on the baseline of the
+    /// first line, at the left edge of the box. Columns of vertical text are wrapped to the
+    /// height of the box instead, with the origin at its top.
+    /// # Arguments
+    /// * `text` - The text to lay out
+    /// * `style` - The style the text will be drawn with
+    /// * `width` - Width of the box, or its height for vertical text
+    pub fn layout_in_box(&mut self, text: &str, style: &TextStyle, width: f32) -> TextLayout {
+        self.layout_with_width(text, style, Some(width))
+    }
+
+    /// Computes the logical and ink bounds of a block of text, its lines and its glyphs
+    ///
+    /// The bounds are relative to the position the text is drawn at with `queue_text`.
+    /// # Arguments
+    /// * `text` - The text to measure
+    /// * `style` - The style the text will be drawn with
+    pub fn measure(&mut self, text: &str, style: &TextStyle) -> TextBounds {
+        let layout = self.layout(text, style);
+        self.measure_layout(&layout, style)
+    }
+
+    /// Computes the logical and ink bounds of laid out text, its lines and its glyphs
+    ///
+    /// The ink bounds match the quads the renderer draws for the layout.
+    /// # Arguments
+    /// * `layout` - The laid out text
+    /// * `style` - The style the text was laid out with
+    pub fn measure_layout(&mut self, layout: &TextLayout, style: &TextStyle) -> TextBounds {
+        let glyph_cache = &mut self.glyphs;
+        layout::measure_layout(layout, |glyph| {
+            let ch = glyph_cache.get(glyph.font, glyph.glyph_index).ok()?;
+            let rect = glyph_rect(glyph, ch, style.scale)?;
+
+            // Distance field bitmaps include a margin around the shape which draws nothing
+            let padding = ch.padding as f32 * style.scale;
+            Some(Rect {
+                x: rect.x + padding,
+                y: rect.y + padding,
+                width: rect.width - padding * 2.0,
+                height: rect.height - padding * 2.0,
+            })
+        })
+    }
+
+    /// Finds the grapheme cluster of a block of text drawn with `queue_text` at a point
+    ///
+    /// Positions from SDL mouse events can be converted with `hit_test::window_to_view`.
+    /// # Arguments
+    /// * `text` - The text
+    /// * `position` - Position the text is drawn at
+    /// * `style` - The style the text is drawn with
+    /// * `point` - The point to test, in the same coordinates as `position`
+    pub fn hit_test(
+        &mut self,
+        text: &str,
+        position: (f32, f32),
+        style: &TextStyle,
+        point: (f32, f32),
+    ) -> Option<Hit> {
+        let layout = self.layout(text, style);
+        hit_test::hit_test(text, &layout, (point.0 - position.0, point.1 - position.1))
+    }
+
+    /// Returns the rectangle to draw a caret in, before the character at a byte offset of a
+    /// block of text drawn with `queue_text`
+    /// # Arguments
+    /// * `text` - The text
+    /// * `position` - Position the text is drawn at
+    /// * `style` - The style the text is drawn with
+    /// * `index` - Byte offset of the caret in the text
+    /// * `width` - Thickness of the caret
+    pub fn caret_rect(
+        &mut self,
+        text: &str,
+        position: (f32, f32),
+        style: &TextStyle,
+        index: usize,
+        width: f32,
+    ) -> Option<Rect> {
+        let layout = self.layout(text, style);
+        hit_test::caret_rect(text, &layout, index, width).map(|rect| rect.translated(position))
+    }
+
+    /// Queues a block of text, aligned to a point, to be drawn on the next flush
+    /// # Arguments
+    /// * `text` - The text to draw
+    /// * `position` - Position on the first line's baseline that lines are aligned to
+    /// * `style` - The style to draw the text with
+    pub fn queue_text(&mut self, text: &str, position: (f32, f32), style: &TextStyle) {
+        let layout = self.layout(text, style);
+        self.queue_layout(&layout, position, style);
+    }
+
+    /// Queues a block of text, wrapped and aligned within a box, to be drawn on the next flush
+    ///
+    /// The first line touches the top of the box, text that does not fit vertically extends
+    /// below it. The first column of vertical text touches the right of the box, columns that
+    /// do not fit extend to the left of it.
+    /// # Arguments
+    /// * `text` - The text to draw
+    /// * `bounds` - The box to draw the text in
+    /// * `style` - The style to draw the text with
+    pub fn queue_text_in_box(&mut self, text: &str, bounds: &Rect, style: &TextStyle) {
+        let width = match style.writing_mode {
+            WritingMode::Horizontal => bounds.width,
+            WritingMode::Vertical => bounds.height,
+        };
+        let layout = self.layout_in_box(text, style, width);
+
+        let origin = match style.writing_mode {
+            WritingMode::Horizontal => {
+                // Move the top of the first line to the top of the box
+                let ascender = layout.bounds.y + layout.bounds.height;
+                (bounds.x, bounds.y + bounds.height - ascender)
+            }
+            WritingMode::Vertical => {
+                // Move the right of the first column to the right of the box
+                let right = layout.bounds.x + layout.bounds.width;
+                (bounds.x + bounds.width - right, bounds.y + bounds.height)
+            }
+        };
+
+        self.queue_layout(&layout, origin, style);
+    }
+
+    /// Draws all queued text with one upload and one draw call per atlas page
+    ///
+    /// Shadows and glows are drawn first, each distinct blur and color with its own offscreen
+    /// pass.
+    pub fn flush(&mut self) {
+        self.flush_effects();
+        self.program.set_used();
+
+        match self.compositing {
+            Compositing::Alpha => self.batch.flush(self.glyphs.atlas(), self.color_glyphs_id),
+            Compositing::DualSource => {
+                let previous = gl_util::blend_function();
+
+                // Blend each channel towards the text color by that channel's coverage
+                gl_util::set_blend_function([
+                    gl::SRC1_COLOR,
+                    gl::ONE_MINUS_SRC1_COLOR,
+                    gl::ONE,
+                    gl::ONE_MINUS_SRC_ALPHA,
+                ]);
+                self.batch.flush(self.glyphs.atlas(), self.color_glyphs_id);
+
+                gl_util::set_blend_function(previous);
+            }
+            Compositing::PerChannel => {
+                let previous = gl_util::blend_function();
+                gl_util::set_blend_function([
+                    gl::SRC_ALPHA,
+                    gl::ONE_MINUS_SRC_ALPHA,
+                    gl::ONE,
+                    gl::ONE_MINUS_SRC_ALPHA,
+                ]);
+
+                // Draw the same quads once per channel, only writing to that channel
+                self.batch.upload();
+                for channel in 0..3 {
+                    unsafe {
+                        gl::ColorMask(
+                            (channel == 0) as u8,
+                            (channel == 1) as u8,
+                            (channel == 2) as u8,
+                            gl::FALSE,
+                        );
+                    }
+                    gl_util::set_uniform_int_at(self.channel_id, channel);
+                    self.batch.draw(self.glyphs.atlas(), self.color_glyphs_id);
+                }
+                self.batch.clear();
+
+                unsafe {
+                    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                }
+                gl_util::set_blend_function(previous);
+            }
+        }
+    }
+
+    /// Draws the queued shadows and glows, blurring the coverage of their text offscreen
+    fn flush_effects(&mut self) {
+        for (effect, batch) in self.effects.iter_mut() {
+            if batch.is_empty() {
+                continue;
+            }
+
+            if self.post.begin().is_err() {
+                batch.clear();
+                continue;
+            }
+
+            self.effect_program.set_used();
+            batch.flush(self.glyphs.atlas(), self.effect_color_glyphs_id);
+            self.post.end(effect);
+        }
+    }
+
+    /// Queues a quad of text, along with the shadows and glows of the text's style
+    /// # Arguments
+    /// * `layer` - The batch layer to draw the quad in
+    /// * `region` - Region of the atlas to draw
+    /// * `rect` - The area covered by the quad
+    /// * `sideways` - Whether the region is drawn rotated 90 degrees clockwise
+    /// * `color` - Color of the quad
+    /// * `style` - The style the text is drawn with
+    fn push_quad(
+        &mut self,
+        layer: usize,
+        region: &AtlasRegion,
+        rect: &Rect,
+        sideways: bool,
+        color: Color,
+        style: &TextStyle,
+    ) {
+        let push = if sideways {
+            QuadBatch::push_rotated_quad
+        } else {
+            QuadBatch::push_quad
+        };
+
+        push(
+            &mut self.batch,
+            layer,
+            region.page,
+            (rect.x, rect.y),
+            (rect.width, rect.height),
+            &region.uv,
+            color,
+        );
+
+        // Effects only keep the coverage of the text, their color is applied once blurred
+        let effects = style
+            .shadow
+            .map(|shadow| (Effect::from(shadow), shadow.offset))
+            .into_iter()
+            .chain(style.glow.map(|glow| (Effect::from(glow), (0.0, 0.0))));
+        for (effect, offset) in effects {
+            push(
+                self.effect_batch(effect),
+                0,
+                region.page,
+                (rect.x + offset.0, rect.y + offset.1),
+                (rect.width, rect.height),
+                &region.uv,
+                Color::rgba(1.0, 1.0, 1.0, color.a),
+            );
+        }
+    }
+
+    /// Returns the batch collecting the quads of an effect
+    ///
+    /// Batches left empty by the last flush are reused for new effects.
+    /// # Arguments
+    /// * `effect` - The blur, color and blending of the effect
+    fn effect_batch(&mut self, effect: Effect) -> &mut QuadBatch {
+        let index = match self.effects.iter().position(|(e, _)| *e == effect) {
+            Some(index) => index,
+            None => match self.effects.iter().position(|(_, batch)| batch.is_empty()) {
+                Some(index) => {
+                    self.effects[index].0 = effect;
+                    index
+                }
+                None => {
+                    self.effects.push((effect, QuadBatch::new()));
+                    self.effects.len() - 1
+                }
+            },
+        };
+
+        &mut self.effects[index].1
+    }
+
+    /// Breaks text into lines and positions its glyphs
+    /// # Arguments
+    /// * `text` - The text to lay out
+    /// * `style` - The style the text will be drawn with
+    /// * `width` - Width of the box to wrap and align lines within, if any
+    fn layout_with_width(
+        &mut self,
+        text: &str,
+        style: &TextStyle,
+        width: Option<f32>,
+    ) -> TextLayout {
+        let mut lines = vec![];
+        for range in layout::line_ranges(text) {
+            let paragraph = Paragraph::new(text, range.clone(), style.direction);
+            let rtl = paragraph.is_rtl();
+            let glyphs = self.shape_range(text, range.clone(), style, &paragraph);
+
+            let max_width = match width.or(style.max_width) {
+                Some(max_width) => max_width,
+                None => {
+                    let metrics = self.line_metrics(text, range.start, &glyphs, style);
+                    lines.push(ShapedLine {
+                        range,
+                        glyphs,
+                        paragraph_end: true,
+                        rtl,
+                        metrics,
+                    });
+                    continue;
+                }
+            };
+
+            // Lines that were wrapped are shaped again, as shaping may differ at the break
+            let wrapped = wrap::wrap_line(text, range.clone(), &glyphs, max_width);
+            if wrapped.len() == 1 {
+                let metrics = self.line_metrics(text, range.start, &glyphs, style);
+                lines.push(ShapedLine {
+                    range,
+                    glyphs,
+                    paragraph_end: true,
+                    rtl,
+                    metrics,
+                });
+            } else {
+                let count = wrapped.len();
+                for (i, range) in wrapped.into_iter().enumerate() {
+                    let glyphs = self.shape_range(text, range.clone(), style, &paragraph);
+                    let metrics = self.line_metrics(text, range.start, &glyphs, style);
+                    lines.push(ShapedLine {
+                        range,
+                        glyphs,
+                        paragraph_end: i == count - 1,
+                        rtl,
+                        metrics,
+                    });
+                }
+            }
+        }
+
+        let options = LayoutOptions {
+            line_height: style.line_height,
+            align: style.align,
+            width,
+            writing_mode: style.writing_mode,
+        };
+
+        layout::layout_lines(text, lines, &options)
+    }
+
+    /// Queues a solid rectangle to be drawn behind all text on the next flush, such as a
+    /// selection highlight or a caret
+    /// # Arguments
+    /// * `rect` - The area to fill
+    /// * `color` - Color of the rectangle
+    pub fn queue_rect(&mut self, rect: &Rect, color: Color) {
+        if let Ok(solid) = self.glyphs.solid() {
+            self.batch.push_quad(
+                BACKGROUND_LAYER,
+                solid.page,
+                (rect.x, rect.y),
+                (rect.width, rect.height),
+                &solid.uv,
+                color,
+            );
+        }
+    }
+
+    /// Queues the glyphs of a layout to be drawn on the next flush
+    ///
+    /// Outlines are queued in a layer behind the glyphs.
+    /// # Arguments
+    /// * `layout` - The laid out text, from `layout` or `layout_in_box`
+    /// * `origin` - Position to draw the layout's origin at
+    /// * `style` - The style the text was laid out with
+    pub fn queue_layout(&mut self, layout: &TextLayout, origin: (f32, f32), style: &TextStyle) {
+        for glyph in layout.lines.iter().flat_map(|line| line.glyphs.iter()) {
+            let ch = match self.glyphs.get(glyph.font, glyph.glyph_index) {
+                Ok(character) => *character,
+                Err(_) => continue,
+            };
+
+            let attributes = self.attributes_at(style, glyph.cluster);
+            if let Some(outline) = attributes.outline {
+                self.queue_outline(glyph, origin, &outline, style);
+            }
+
+            // Glyphs without a bitmap (such as spaces) are not drawn
+            if let Some(rect) = glyph_rect(glyph, &ch, style.scale) {
+                let rect = rect.translated(origin);
+                let region = AtlasRegion {
+                    page: ch.page,
+                    uv: ch.uv,
+                };
+                let color = attributes.color;
+                self.push_quad(TEXT_LAYER, &region, &rect, glyph.sideways, color, style);
+            }
+        }
+
+        for line in layout.lines.iter() {
+            self.queue_decorations(line, origin, style);
+        }
+    }
+
+    /// Queues the outline of a glyph behind the glyphs
+    /// # Arguments
+    /// * `glyph` - The glyph's position in the layout
+    /// * `origin` - Position to draw the layout's origin at
+    /// * `outline` - The outline to draw
+    /// * `style` - The style the text was laid out with
+    fn queue_outline(
+        &mut self,
+        glyph: &PositionedGlyph,
+        origin: (f32, f32),
+        outline: &Outline,
+        style: &TextStyle,
+    ) {
+        let stroke = self.glyphs.stroked(
+            &self.stroker,
+            glyph.font,
+            glyph.glyph_index,
+            outline.width,
+            outline.join,
+        );
+        let ch = match stroke {
+            Ok(character) => *character,
+            Err(_) => return,
+        };
+
+        if let Some(rect) = glyph_rect(glyph, &ch, style.scale) {
+            let rect = rect.translated(origin);
+            let region = AtlasRegion {
+                page: ch.page,
+                uv: ch.uv,
+            };
+            let color = outline.color;
+            self.push_quad(OUTLINE_LAYER, &region, &rect, glyph.sideways, color, style);
+        }
+    }
+
+    /// Queues the underlines and strikethrough lines of a line of text
+    ///
+    /// Consecutive glyphs with the same font, color, decoration and outline share a single line.
+    /// Outlined lines are outlined with a rectangle behind them. The lines of vertical text run
+    /// down the column, to the left of its center line for underlines.
+    /// # Arguments
+    /// * `line` - The laid out line
+    /// * `origin` - Position to draw the layout's origin at
+    /// * `style` - The style the text was laid out with
+    fn queue_decorations(&mut self, line: &Line, origin: (f32, f32), style: &TextStyle) {
+        if line.glyphs.is_empty() {
+            return;
+        }
+
+        let solid = match self.glyphs.solid() {
+            Ok(region) => region,
+            Err(_) => return,
+        };
+
+        // Runs of glyphs sharing a decoration, as (font, color, decoration, outline, start, end)
+        let mut runs: Vec<(FontInstance, Color, Decoration, Option<Outline>, f32, f32)> = vec![];
+        for glyph in line.glyphs.iter() {
+            let attributes = self.attributes_at(style, glyph.cluster);
+            if attributes.decoration.is_none() {
+                continue;
+            }
+
+            let (start, end) = line.glyph_extent(glyph);
+
+            match runs.last_mut() {
+                Some(run)
+                    if run.0 == glyph.font
+                        && run.1 == attributes.color
+                        && run.2 == attributes.decoration
+                        && run.3 == attributes.outline
+                        && (run.5 - start).abs() < 0.5 =>
+                {
+                    run.5 = end;
+                }
+                _ => runs.push((
+                    glyph.font,
+                    attributes.color,
+                    attributes.decoration,
+                    attributes.outline,
+                    start,
+                    end,
+                )),
+            }
+        }
+
+        for (font, color, decoration, outline, start, end) in runs {
+            let mut lines = vec![];
+            if decoration.underline {
+                lines.push(self.glyphs.underline(font));
+            }
+            if decoration.strikethrough {
+                lines.push(self.glyphs.strikeout(font));
+            }
+
+            for metrics in lines {
+                let thickness = metrics.thickness * style.scale;
+                let bottom = metrics.position * style.scale - thickness / 2.0;
+
+                let rect = line
+                    .area((start, end), (bottom, bottom + thickness))
+                    .translated(origin);
+
+                if let Some(outline) = outline {
+                    let width = outline.width * style.scale;
+                    let outline_rect = Rect {
+                        x: rect.x - width,
+                        y: rect.y - width,
+                        width: rect.width + width * 2.0,
+                        height: rect.height + width * 2.0,
+                    };
+                    self.push_quad(
+                        OUTLINE_LAYER,
+                        &solid,
+                        &outline_rect,
+                        false,
+                        outline.color,
+                        style,
+                    );
+                }
+
+                self.push_quad(TEXT_LAYER, &solid, &rect, false, color, style);
+            }
+        }
+    }
+
+    /// Returns the attributes of the character at a byte offset
+    /// # Arguments
+    /// * `style` - The style the text is drawn with
+    /// * `index` - Byte offset of the character
+    fn attributes_at<'a>(&self, style: &'a TextStyle, index: usize) -> Attributes<'a> {
+        let base = Attributes {
+            family: style.family.as_deref(),
+            size: style.size,
+            weight: style.weight,
+            slant: style.slant,
+            color: style.color,
+            decoration: style.decoration,
+            variations: &style.variations,
+            outline: style.outline.filter(|outline| outline.width > 0.0),
+        };
+
+        span::attributes_at(base, &style.spans, index)
+    }
+
+    /// Returns the font, size and variation to draw the character at a byte offset with
+    ///
+    /// The best match for the family, weight and slant is used. Text of a family that is not
+    /// loaded is drawn with the renderer's font family.
+    /// # Arguments
+    /// * `style` - The style the text is drawn with
+    /// * `index` - Byte offset of the character
+    fn font_at(&mut self, style: &TextStyle, index: usize) -> FontInstance {
+        let attributes = self.attributes_at(style, index);
+        let fonts = self.glyphs.fonts();
+
+        let family = attributes
+            .family
+            .and_then(|family| font::find_font(fonts, family, attributes.weight, attributes.slant));
+        let font = match family {
+            Some(font) => font,
+            None => {
+                let default_family = self.glyphs.font(self.font).family();
+                font::find_font(fonts, default_family, attributes.weight, attributes.slant)
+                    .unwrap_or(self.font)
+            }
+        };
+
+        self.instance(font, &attributes)
+    }
+
+    /// Returns the instance of a font to draw text with a set of attributes with
+    ///
+    /// Variable fonts with weight or optical size axes are set to the attributes' weight and font
+    /// size, unless the attributes set those axes explicitly.
+    /// # Arguments
+    /// * `font` - The font to draw with
+    /// * `attributes` - The attributes of the text
+    fn instance(&mut self, font: FontId, attributes: &Attributes) -> FontInstance {
+        let size = attributes.size.unwrap_or(self.font_size);
+
+        let mut settings = vec![
+            Variation::new(b"wght", attributes.weight.0 as f32),
+            Variation::new(b"opsz", size),
+        ];
+        settings.extend_from_slice(attributes.variations);
+
+        FontInstance {
+            variation: self.glyphs.variation(font, &settings),
+            ..FontInstance::new(font, size)
+        }
+    }
+
+    /// Returns the fonts to try for the character at a byte offset, in order of preference
+    ///
+    /// The chain starts with the font selected for the character, followed by the best match of
+    /// each of the style's fallback families that is loaded.
+    /// # Arguments
+    /// * `style` - The style the text is drawn with
+    /// * `index` - Byte offset of the character
+    /// * `primary` - The font selected for the character
+    fn fallback_chain(&self, style: &TextStyle, index: usize, primary: FontId) -> Vec<FontId> {
+        let attributes = self.attributes_at(style, index);
+        let fonts = self.glyphs.fonts();
+
+        let mut chain = vec![primary];
+        for family in style.fallback.iter() {
+            if let Some(font) = font::find_font(fonts, family, attributes.weight, attributes.slant)
+            {
+                if !chain.contains(&font) {
+                    chain.push(font);
+                }
+            }
+        }
+
+        chain
+    }
+
+    /// Returns the combined metrics of the fonts used on a line, scaled by the style
+    ///
+    /// The metrics of vertical text are centered on the column's center line.
+    /// # Arguments
+    /// * `text` - The text containing the line
+    /// * `start` - Byte offset of the start of the line
+    /// * `glyphs` - The shaped glyphs of the line
+    /// * `style` - The style the text is drawn with
+    fn line_metrics(
+        &mut self,
+        text: &str,
+        start: usize,
+        glyphs: &[ShapedGlyph],
+        style: &TextStyle,
+    ) -> FontMetrics {
+        // Lines without glyphs still take up the height of the font at their start
+        let first = self.font_at(style, start.min(text.len()));
+
+        let mut fonts = vec![first];
+        for glyph in glyphs.iter() {
+            if !fonts.contains(&glyph.font) {
+                fonts.push(glyph.font);
+            }
+        }
+
+        let mut metrics = self.glyphs.metrics(first);
+        for font in fonts.into_iter().skip(1) {
+            metrics = metrics.max(&self.glyphs.metrics(font));
+        }
+
+        match style.writing_mode {
+            WritingMode::Horizontal => metrics.scaled(style.scale),
+            WritingMode::Vertical => metrics.centered().scaled(style.scale),
+        }
+    }
+
+    /// Shapes part of a piece of text, keeping clusters relative to the whole text
+    ///
+    /// The glyphs are returned in visual order, with runs of left to right and right to left
+    /// text reordered for display on a single line. In vertical text, characters of scripts
+    /// written vertically are shaped top to bottom and the rest is shaped horizontally and turned
+    /// sideways.
+    /// # Arguments
+    /// * `text` - The text containing the range
+    /// * `range` - Byte range of a single line of text to shape
+    /// * `style` - The style the text will be drawn with
+    /// * `paragraph` - The bidirectional levels of the paragraph containing the line
+    fn shape_range(
+        &mut self,
+        text: &str,
+        range: Range<usize>,
+        style: &TextStyle,
+        paragraph: &Paragraph,
+    ) -> Vec<ShapedGlyph> {
+        let levels = paragraph.line_levels(range.clone());
+        let line_start = range.start;
+
+        // Split the text into runs drawn with a single font, size, direction and orientation, each
+        // grapheme cluster is drawn with the first font of the fallback chain that has glyphs for
+        // all of it
+        let vertical = style.writing_mode == WritingMode::Vertical;
+        let mut runs: Vec<(Range<usize>, FontInstance, u8, bool)> = vec![];
+        for segment in span::segments(&style.spans, range) {
+            let primary = self.font_at(style, segment.start);
+            let chain = self.fallback_chain(style, segment.start, primary.font);
+
+            for (i, grapheme) in text[segment.clone()].grapheme_indices(true) {
+                let start = segment.start + i;
+                let end = start + grapheme.len();
+
+                // Fallback fonts have their own axes, if any
+                let font = match self.glyphs.fallback(&chain, grapheme) {
+                    font if font == primary.font => primary,
+                    font => {
+                        let attributes = self.attributes_at(style, segment.start);
+                        self.instance(font, &attributes)
+                    }
+                };
+
+                let level = levels[start - line_start];
+                let upright = vertical && grapheme.chars().next().is_some_and(vertical::is_upright);
+                match runs.last_mut() {
+                    Some(run)
+                        if run.1 == font
+                            && run.2 == level
+                            && run.3 == upright
+                            && run.0.end == start =>
+                    {
+                        run.0.end = end
+                    }
+                    _ => runs.push((start..end, font, level, upright)),
+                }
+            }
+        }
+
+        // Runs are shaped in logical order, each in its own direction
+        let mut shaped_runs = Vec::with_capacity(runs.len());
+        for (run, font, level, upright) in runs.iter().cloned() {
+            let direction = if upright {
+                RunDirection::TopToBottom
+            } else if level % 2 == 1 {
+                RunDirection::RightToLeft
+            } else {
+                RunDirection::LeftToRight
+            };
+            let shaped = match self.glyphs.font(font.font).shaper() {
+                Some(shaper) => {
+                    // Kerning is applied through the GPOS and kern tables when shaping
+                    let features = [shaping::feature(b"kern", style.kerning)];
+                    let pixels_per_em = font.pixels_per_em(self.glyphs.dpi());
+                    let variations = self.glyphs.variation_values(font.variation);
+                    Some(shaper.shape(
+                        &text[run.clone()],
+                        &features,
+                        variations,
+                        font,
+                        pixels_per_em,
+                        direction,
+                    ))
+                }
+                None => None,
+            };
+
+            let shaped = match shaped {
+                Some(shaped) => shaped,
+                None => self.shape_simple(&text[run.clone()], font, style.kerning, direction),
+            };
+
+            let mut shaped = shaped
+                .into_iter()
+                .map(|mut glyph| {
+                    glyph.cluster += run.start;
+                    glyph.advance = (glyph.advance.0 * style.scale, glyph.advance.1 * style.scale);
+                    glyph.offset = (glyph.offset.0 * style.scale, glyph.offset.1 * style.scale);
+                    glyph
+                })
+                .collect::<Vec<_>>();
+
+            // Horizontal runs in vertical text run down the column on their side
+            if vertical && !upright {
+                let metrics = self.glyphs.metrics(font).scaled(style.scale);
+                vertical::turn_sideways(&mut shaped, &metrics);
+            }
+
+            shaped_runs.push(shaped);
+        }
+
+        // Then placed in the order they are displayed in
+        let run_levels = runs.iter().map(|run| run.2).collect::<Vec<_>>();
+        let mut glyphs = vec![];
+        for i in bidi::visual_order(&run_levels) {
+            glyphs.append(&mut shaped_runs[i]);
+        }
+
+        glyphs
+    }
+
+    /// Lays out text one character at a time using the advances from Freetype
+    ///
+    /// Used when the font can not be shaped. Characters missing from the face are drawn with its
+    /// missing glyph (glyph index 0). Text laid out top to bottom uses the vertical advances
+    /// from Freetype, without kerning.
+    /// # Arguments
+    /// * `text` - The text to lay out
+    /// * `font` - The font and size to lay the text out with
+    /// * `kerning` - Whether to apply kerning from the font's kern table
+    /// * `direction` - The direction to lay the text out in
+    fn shape_simple(
+        &mut self,
+        text: &str,
+        font: FontInstance,
+        kerning: bool,
+        direction: RunDirection,
+    ) -> Vec<ShapedGlyph> {
+        let rtl = direction == RunDirection::RightToLeft;
+        let mut glyphs: Vec<ShapedGlyph> = vec![];
+
+        for (i, c) in text.char_indices() {
+            // Control characters such as line breaks are never drawn
+            if c.is_control() {
+                continue;
+            }
+
+            let glyph_index = self.glyphs.glyph_index(font.font, c);
+
+            if direction == RunDirection::TopToBottom {
+                if let Ok((advance, offset)) = self.glyphs.vertical_metrics(font, glyph_index) {
+                    glyphs.push(ShapedGlyph {
+                        font,
+                        glyph_index,
+                        cluster: i,
+                        advance: (0.0, -advance),
+                        offset,
+                        rtl,
+                        sideways: false,
+                    });
+                }
+                continue;
+            }
+
+            let ch = match self.glyphs.get(font, glyph_index) {
+                Ok(character) => *character,
+                Err(_) => continue,
+            };
+
+            // Kerning adjusts the distance from the previous glyph to this one
+            if kerning {
+                if let Some(previous) = glyphs.last_mut() {
+                    previous.advance.0 +=
+                        self.glyphs.kerning(font, previous.glyph_index, glyph_index);
+                }
+            }
+
+            glyphs.push(ShapedGlyph {
+                font,
+                glyph_index,
+                cluster: i,
+                advance: (ch.advance as f32 / 64.0, 0.0),
+                offset: (0.0, 0.0),
+                rtl,
+                sideways: false,
+            });
+        }
+
+        // Right to left text is displayed starting with its last character
+        if rtl {
+            glyphs.reverse();
+        }
+
+        glyphs
+    }
+
+    /// Draws a block of text immediately, aligned to a position by the style's `align`, which
+    /// defaults to `Alignment::Start`
+    ///
+    /// Any previously queued text is drawn as well. When drawing many strings prefer
+    /// `queue_text` followed by a single `flush`.
+    /// # Arguments
+    /// * `text` - The text to draw
+    /// * `position` - Position on the first line's baseline that lines are aligned to
+    /// * `style` - The style to draw the text with
+    pub fn draw_text(&mut self, text: &str, position: (f32, f32), style: &TextStyle) {
+        self.queue_text(text, position, style);
+        self.flush();
+    }
+}
+
+/// Returns the area covered by a glyph's bitmap relative to the layout origin
+///
+/// The bitmaps of glyphs turned sideways are turned about the glyph's position.
+/// # Arguments
+/// * `glyph` - The glyph's position in the layout
+/// * `ch` - The rasterized glyph
+/// * `scale` - Multiplier applied to the rasterized glyph size
+fn glyph_rect(glyph: &PositionedGlyph, ch: &Character, scale: f32) -> Option<Rect> {
+    if ch.size.0 == 0 || ch.size.1 == 0 {
+        return None;
+    }
+
+    // Character units are expressed in 26.6 pixel format (1/64th of a pixel)
+    /*
+    For pixel perfect font rendering we need to apply the correct transformation to the view space.
+    This involves determining the conversion of 'font pixels' to 'double unit cube' coordinates.
+    Effectively this is a translation and scaling in the X and Y axes (aka an orthographic projection)
+    This is different than the orthographic projection that would be normally used for transforming 'world coordinates'
+    to 'view space' coordinates.
+    */
+
+    let rect = Rect {
+        x: ch.bearing.0 as f32 * scale,
+        y: -(ch.size.1 - ch.bearing.1) as f32 * scale,
+        width: ch.size.0 as f32 * scale,
+        height: ch.size.1 as f32 * scale,
+    };
+
+    if glyph.sideways {
+        Some(rect.turned().translated(glyph.position))
+    } else {
+        Some(rect.translated(glyph.position))
+    }
+}
+
+/// Creates the shader program used to render text
+/// # Arguments
+/// * `mode` - How the glyph bitmaps being drawn were generated
+/// * `compositing` - How drawn glyphs are combined with the framebuffer
+fn create_program(mode: GlyphMode, compositing: Compositing) -> Result<Program, String> {
+    // Load shaders
+    let vertex_shader = match Shader::new_from_source(VERTEX_SHADER, gl::VERTEX_SHADER) {
+        Ok(shader) => shader,
+        Err(message) => return Err(format!("Failed to create vertex shader: {}", message)),
+    };
+
+    let fragment_source = match (mode, compositing) {
+        (GlyphMode::DistanceField, _) => SDF_FRAGMENT_SHADER,
+        (_, Compositing::DualSource) => LCD_FRAGMENT_SHADER,
+        (_, Compositing::PerChannel) => LCD_CHANNEL_FRAGMENT_SHADER,
+        _ => FRAGMENT_SHADER,
+    };
+
+    let fragment_shader = match Shader::new_from_source(fragment_source, gl::FRAGMENT_SHADER) {
+        Ok(shader) => shader,
+        Err(message) => return Err(format!("Failed to create fragment shader: {}", message)),
+    };
+
+    // Create shader program
+    match Program::new()
+        .attach_shader(&vertex_shader)
+        .attach_shader(&fragment_shader)
+        .link()
+    {
+        Ok(program) => Ok(program),
+        Err(message) => Err(format!("Failed to create shader program: {}", message)),
+    }
+}
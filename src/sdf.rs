@@ -0,0 +1,302 @@
+use freetype::outline::{Curve, Outline};
+
+/// Number of straight segments each curve of an outline is approximated with
+const CURVE_SEGMENTS: usize = 8;
+
+/// A single channel signed distance field of a glyph
+///
+/// Each pixel stores the distance from its center to the closest point of the outline, mapped
+/// so that 128 lies on the outline, larger values are inside the glyph and smaller values are
+/// outside of it. Distances further than the spread are clamped.
+#[derive(Clone, Debug)]
+pub struct DistanceField {
+    /// Width of the field in pixels
+    pub width: u32,
+    /// Height of the field in pixels
+    pub height: u32,
+    /// Offset from the pen position to the left edge of the field in pixels
+    pub left: i32,
+    /// Offset from the pen position to the top edge of the field in pixels
+    pub top: i32,
+    /// Tightly packed distance values, top row first
+    pub data: Vec<u8>,
+}
+
+/// A straight section of a flattened outline
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: (f32, f32),
+    end: (f32, f32),
+}
+
+impl Segment {
+    /// Returns the squared distance from a point to the closest point on the segment
+    /// # Arguments
+    /// * `point` - The point to measure from
+    fn distance_squared(&self, point: (f32, f32)) -> f32 {
+        let direction = (self.end.0 - self.start.0, self.end.1 - self.start.1);
+        let relative = (point.0 - self.start.0, point.1 - self.start.1);
+
+        // Project the point onto the segment, keeping it between the end points
+        let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+        let t = if length_squared > 0.0 {
+            ((relative.0 * direction.0 + relative.1 * direction.1) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let dx = relative.0 - direction.0 * t;
+        let dy = relative.1 - direction.1 * t;
+        dx * dx + dy * dy
+    }
+
+    /// Returns the winding contribution of a ray cast from a point in the positive x direction
+    /// # Arguments
+    /// * `point` - The point the ray starts at
+    fn winding(&self, point: (f32, f32)) -> i32 {
+        let (start, end) = (self.start, self.end);
+
+        // Each crossing counts once, so the segment is half open in y
+        let upward = start.1 <= point.1 && end.1 > point.1;
+        let downward = end.1 <= point.1 && start.1 > point.1;
+        if !upward && !downward {
+            return 0;
+        }
+
+        // Find where the segment crosses the ray's height
+        let t = (point.1 - start.1) / (end.1 - start.1);
+        let x = start.0 + (end.0 - start.0) * t;
+        if x <= point.0 {
+            return 0;
+        }
+
+        if upward {
+            1
+        } else {
+            -1
+        }
+    }
+}
+
+/// Generates a signed distance field from a glyph outline
+///
+/// Returns None for outlines without any contours, such as spaces.
+/// # Arguments
+/// * `outline` - The glyph outline, in 26.6 fixed point pixels
+/// * `spread` - The largest distance stored in the field and the margin around the outline, in pixels
+pub fn from_outline(outline: &Outline, spread: u32) -> Option<DistanceField> {
+    from_outline_expanded(outline, spread, 0.0)
+}
+
+/// Generates a signed distance field of a glyph outline grown outwards by a distance
+///
+/// The edge of the field lies at the given distance outside of the outline, which strokes the
+/// glyph with rounded corners.
+/// # Arguments
+/// * `outline` - The glyph outline, in 26.6 fixed point pixels
+/// * `spread` - The largest distance stored in the field and the margin around the outline
+/// * `expand` - Distance to grow the outline by, in pixels
+pub fn from_outline_expanded(outline: &Outline, spread: u32, expand: f32) -> Option<DistanceField> {
+    let segments = flatten(outline);
+    if segments.is_empty() {
+        return None;
+    }
+
+    // Find the bounds of the outline
+    let mut min = (f32::MAX, f32::MAX);
+    let mut max = (f32::MIN, f32::MIN);
+    for segment in segments.iter() {
+        for point in [segment.start, segment.end].iter() {
+            min = (min.0.min(point.0), min.1.min(point.1));
+            max = (max.0.max(point.0), max.1.max(point.1));
+        }
+    }
+
+    // Leave room for the grown outline and the distances outside of it on every side
+    let margin = spread as i32 + expand.ceil() as i32;
+    let left = min.0.floor() as i32 - margin;
+    let bottom = min.1.floor() as i32 - margin;
+    let right = max.0.ceil() as i32 + margin;
+    let top = max.1.ceil() as i32 + margin;
+
+    let width = (right - left) as u32;
+    let height = (top - bottom) as u32;
+    let mut data = vec![0u8; (width * height) as usize];
+
+    for row in 0..height {
+        for column in 0..width {
+            // Sample at the pixel's center, rows are stored from the top down
+            let point = (
+                left as f32 + column as f32 + 0.5,
+                top as f32 - row as f32 - 0.5,
+            );
+
+            let mut distance_squared = f32::MAX;
+            let mut winding = 0;
+            for segment in segments.iter() {
+                distance_squared = distance_squared.min(segment.distance_squared(point));
+                winding += segment.winding(point);
+            }
+
+            // Outlines use the non-zero fill rule
+            let distance = if winding != 0 {
+                distance_squared.sqrt()
+            } else {
+                -distance_squared.sqrt()
+            };
+
+            let value = 0.5 + (distance + expand) / (2.0 * spread as f32);
+            data[(row * width + column) as usize] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+
+    Some(DistanceField {
+        width,
+        height,
+        left,
+        top,
+        data,
+    })
+}
+
+/// Approximates the contours of an outline with straight segments
+/// # Arguments
+/// * `outline` - The outline to flatten, in 26.6 fixed point pixels
+fn flatten(outline: &Outline) -> Vec<Segment> {
+    let to_pixels = |v: &freetype::Vector| (v.x as f32 / 64.0, v.y as f32 / 64.0);
+    let mut segments = vec![];
+
+    for contour in outline.contours_iter() {
+        let mut points = vec![to_pixels(contour.start())];
+
+        for curve in contour {
+            let p0 = *points.last().unwrap();
+
+            match curve {
+                Curve::Line(end) => points.push(to_pixels(&end)),
+                Curve::Bezier2(control, end) => {
+                    let (p1, p2) = (to_pixels(&control), to_pixels(&end));
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let u = 1.0 - t;
+                        points.push((
+                            u * u * p0.0 + 2.0 * u * t * p1.0 + t * t * p2.0,
+                            u * u * p0.1 + 2.0 * u * t * p1.1 + t * t * p2.1,
+                        ));
+                    }
+                }
+                Curve::Bezier3(control1, control2, end) => {
+                    let (p1, p2, p3) =
+                        (to_pixels(&control1), to_pixels(&control2), to_pixels(&end));
+                    for i in 1..=CURVE_SEGMENTS {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let u = 1.0 - t;
+                        points.push((
+                            u * u * u * p0.0
+                                + 3.0 * u * u * t * p1.0
+                                + 3.0 * u * t * t * p2.0
+                                + t * t * t * p3.0,
+                            u * u * u * p0.1
+                                + 3.0 * u * u * t * p1.1
+                                + 3.0 * u * t * t * p2.1
+                                + t * t * t * p3.1,
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Close the contour if the last curve did not end at its start
+        if points.last() != points.first() {
+            points.push(points[0]);
+        }
+
+        segments.extend(points.windows(2).map(|pair| Segment {
+            start: pair[0],
+            end: pair[1],
+        }));
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: (f32, f32), end: (f32, f32)) -> Segment {
+        Segment { start, end }
+    }
+
+    /// Sum of the winding contributions of a closed path through the points
+    fn winding(points: &[(f32, f32)], point: (f32, f32)) -> i32 {
+        (0..points.len())
+            .map(|i| segment(points[i], points[(i + 1) % points.len()]).winding(point))
+            .sum()
+    }
+
+    #[test]
+    fn distance_to_a_point_beside_the_segment() {
+        let horizontal = segment((0.0, 0.0), (10.0, 0.0));
+
+        assert_eq!(horizontal.distance_squared((4.0, 3.0)), 9.0);
+        assert_eq!(horizontal.distance_squared((4.0, 0.0)), 0.0);
+        assert_eq!(
+            segment((0.0, 0.0), (4.0, 4.0)).distance_squared((0.0, 4.0)),
+            8.0
+        );
+    }
+
+    #[test]
+    fn distance_past_the_ends_is_to_the_end_points() {
+        let horizontal = segment((0.0, 0.0), (10.0, 0.0));
+
+        assert_eq!(horizontal.distance_squared((-3.0, 4.0)), 25.0);
+        assert_eq!(horizontal.distance_squared((13.0, -4.0)), 25.0);
+    }
+
+    #[test]
+    fn distance_to_a_degenerate_segment_is_to_its_point() {
+        let point = segment((2.0, 2.0), (2.0, 2.0));
+
+        assert_eq!(point.distance_squared((5.0, 6.0)), 25.0);
+        assert_eq!(point.distance_squared((2.0, 2.0)), 0.0);
+    }
+
+    #[test]
+    fn winding_counts_crossings_right_of_the_point() {
+        let upward = segment((5.0, 0.0), (5.0, 10.0));
+
+        assert_eq!(upward.winding((0.0, 5.0)), 1);
+        assert_eq!(segment((5.0, 10.0), (5.0, 0.0)).winding((0.0, 5.0)), -1);
+        assert_eq!(upward.winding((6.0, 5.0)), 0);
+        assert_eq!(upward.winding((0.0, 11.0)), 0);
+    }
+
+    #[test]
+    fn winding_ignores_horizontal_and_degenerate_segments() {
+        assert_eq!(segment((5.0, 5.0), (10.0, 5.0)).winding((0.0, 5.0)), 0);
+        assert_eq!(segment((5.0, 5.0), (5.0, 5.0)).winding((0.0, 5.0)), 0);
+    }
+
+    #[test]
+    fn winding_is_half_open_at_vertices() {
+        let upward = segment((5.0, 0.0), (5.0, 10.0));
+
+        // The lower end point is included and the upper one is not
+        assert_eq!(upward.winding((0.0, 0.0)), 1);
+        assert_eq!(upward.winding((0.0, 10.0)), 0);
+    }
+
+    #[test]
+    fn rays_through_vertices_cross_a_contour_once() {
+        let diamond = [(5.0, 0.0), (10.0, 5.0), (5.0, 10.0), (0.0, 5.0)];
+
+        // The ray passes through the right vertex, shared by two segments
+        assert_eq!(winding(&diamond, (5.0, 5.0)).abs(), 1);
+        // The ray only touches the top and bottom vertices
+        assert_eq!(winding(&diamond, (0.0, 0.0)), 0);
+        assert_eq!(winding(&diamond, (0.0, 10.0)), 0);
+        assert_eq!(winding(&diamond, (11.0, 5.0)), 0);
+    }
+}
@@ -31,6 +31,14 @@ impl Shader {
             Err(message) => panic!(format!("Shader creation failed: {}", message)),
         };
 
+        Shader::new_from_source(&source, kind)
+    }
+
+    /// Returns a new shader object from source code
+    /// # Arguments
+    /// * `source` - The shader's source code
+    /// * `kind` - The type of shader to create, i.e. vertext, fragment, compute, etc...
+    pub fn new_from_source(source: &str, kind: GLenum) -> Result<Shader, String> {
         // Create a shader object on the GPU
         let id = unsafe { gl::CreateShader(kind) };
 
@@ -0,0 +1,33 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 texture_coordinate;
+
+uniform sampler2D texture_sampler;
+
+// Distance between neighbouring samples, one texel along the blurred axis
+uniform vec2 direction;
+
+// Standard deviation of the Gaussian in pixels
+uniform float sigma;
+
+out vec4 Color;
+
+void main() {
+
+    // The kernel reaches three standard deviations, where the weights become negligible
+    int radius = min(int(ceil(sigma * 3.0)), 64);
+    float denominator = 2.0 * sigma * sigma;
+
+    vec4 sum = texture(texture_sampler, texture_coordinate);
+    float total = 1.0;
+    for (int i = 1; i <= radius; i++) {
+        float weight = exp(-float(i * i) / denominator);
+        sum += texture(texture_sampler, texture_coordinate + direction * float(i)) * weight;
+        sum += texture(texture_sampler, texture_coordinate - direction * float(i)) * weight;
+        total += weight * 2.0;
+    }
+
+    Color = sum / total;
+}
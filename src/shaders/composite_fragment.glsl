@@ -0,0 +1,20 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 texture_coordinate;
+
+uniform sampler2D texture_sampler;
+
+// Color of the effect, its alpha fades the effect
+uniform vec4 color;
+
+out vec4 Color;
+
+void main() {
+
+    // Only the blurred coverage is used, the effect takes on a single color
+    float coverage = texture(texture_sampler, texture_coordinate).a;
+
+    Color = vec4(color.rgb, color.a * coverage);
+}
@@ -3,16 +3,27 @@
 precision mediump float;
 
 in vec2 texture_coordinate;
+in vec4 vertex_color;
 
 uniform sampler2D texture_sampler;
 
+// Whether the texture holds color glyphs in premultiplied RGBA rather than coverage
+uniform bool color_glyphs;
+
 out vec4 Color;
 
 void main() {
 
+    // Color glyphs keep their own colors, only the alpha of the text color fades them
+    if (color_glyphs) {
+        vec4 glyph = texture(texture_sampler, texture_coordinate);
+        Color = vec4(glyph.rgb / max(glyph.a, 0.0001), glyph.a * vertex_color.a);
+        return;
+    }
+
     // Get the red channel from the texture and use it as the alpha
     vec4 sampled = vec4(1.0, 1.0, 1.0, texture(texture_sampler, texture_coordinate).r);
 
     // Multiple our text color by the alpha
-    Color = vec4(1.0, 0.0, 1.0, 1.0) * sampled;
+    Color = vertex_color * sampled;
 }
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 texture_coordinate;
+in vec4 vertex_color;
+
+uniform sampler2D texture_sampler;
+
+// The color channel being drawn, only that channel of the framebuffer is written
+uniform int channel;
+
+// Whether the texture holds color glyphs in premultiplied RGBA rather than subpixel coverage
+uniform bool color_glyphs;
+
+out vec4 Color;
+
+void main() {
+
+    // Color glyphs cover every subpixel of a pixel equally and keep their own colors
+    if (color_glyphs) {
+        vec4 glyph = texture(texture_sampler, texture_coordinate);
+        Color = vec4(glyph.rgb / max(glyph.a, 0.0001), glyph.a * vertex_color.a);
+        return;
+    }
+
+    // Use the coverage of the subpixel being drawn as the alpha
+    vec4 sampled = vec4(1.0, 1.0, 1.0, texture(texture_sampler, texture_coordinate)[channel]);
+
+    // Multiple our text color by the alpha
+    Color = vertex_color * sampled;
+}
\ No newline at end of file
@@ -0,0 +1,34 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 texture_coordinate;
+in vec4 vertex_color;
+
+uniform sampler2D texture_sampler;
+
+// Whether the texture holds color glyphs in premultiplied RGBA rather than subpixel coverage
+uniform bool color_glyphs;
+
+// The text color, and the amount of it to blend into each channel of the framebuffer
+layout(location = 0, index = 0) out vec4 Color;
+layout(location = 0, index = 1) out vec4 Coverage;
+
+void main() {
+
+    // Color glyphs cover every subpixel of a pixel equally and keep their own colors
+    if (color_glyphs) {
+        vec4 glyph = texture(texture_sampler, texture_coordinate);
+        float alpha = glyph.a * vertex_color.a;
+
+        Color = vec4(glyph.rgb / max(glyph.a, 0.0001), alpha);
+        Coverage = vec4(alpha);
+        return;
+    }
+
+    // Each channel of the texture holds the coverage of one subpixel
+    vec3 sampled = texture(texture_sampler, texture_coordinate).rgb;
+
+    Color = vertex_color;
+    Coverage = vec4(sampled * vertex_color.a, vertex_color.a);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 330 core
+
+// Position in normalized device coordinates and texture coordinate
+layout(location = 0) in vec4 vertex;
+
+out vec2 texture_coordinate;
+
+void main() {
+    texture_coordinate = vertex.zw;
+    gl_Position = vec4(vertex.xy, 0.0, 1.0);
+}
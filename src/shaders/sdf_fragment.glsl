@@ -0,0 +1,33 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 texture_coordinate;
+in vec4 vertex_color;
+
+uniform sampler2D texture_sampler;
+
+// Whether the texture holds color glyphs in premultiplied RGBA rather than distances
+uniform bool color_glyphs;
+
+out vec4 Color;
+
+void main() {
+
+    // Color glyphs are bitmaps, they keep their own colors and are only faded by the text color
+    if (color_glyphs) {
+        vec4 glyph = texture(texture_sampler, texture_coordinate);
+        Color = vec4(glyph.rgb / max(glyph.a, 0.0001), glyph.a * vertex_color.a);
+        return;
+    }
+
+    // The red channel holds the signed distance to the glyph outline, 0.5 lies on the outline
+    float distance = texture(texture_sampler, texture_coordinate).r;
+
+    // Antialias over roughly one screen pixel, whatever size the glyph is drawn at
+    float width = max(fwidth(distance), 0.0001) * 0.5;
+    float alpha = smoothstep(0.5 - width, 0.5 + width, distance);
+
+    // Multiple our text color by the alpha
+    Color = vertex_color * vec4(1.0, 1.0, 1.0, alpha);
+}
\ No newline at end of file
@@ -1,12 +1,15 @@
 #version 330 core
 
 layout(location = 0) in vec4 vertex;
+layout(location = 1) in vec4 color;
 
 uniform mat4 projection;
 
 out vec2 texture_coordinate;
+out vec4 vertex_color;
 
 void main() {
     texture_coordinate = vertex.zw;
+    vertex_color = color;
     gl_Position = projection * vec4(vertex.xy, 0.0, 1.0);
 }
\ No newline at end of file
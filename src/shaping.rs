@@ -0,0 +1,260 @@
+use crate::font::{FontData, FontInstance};
+use crate::variation::Variation;
+
+/// A glyph positioned by the shaper
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapedGlyph {
+    /// The font and size the glyph is drawn with
+    pub font: FontInstance,
+    /// Index of the glyph in the face
+    pub glyph_index: u32,
+    /// Byte offset in the shaped text of the first character in this glyph's cluster
+    pub cluster: usize,
+    /// Distance to move the pen after drawing this glyph, in pixels
+    pub advance: (f32, f32),
+    /// Offset from the pen position to draw this glyph at, in pixels
+    pub offset: (f32, f32),
+    /// True if the glyph belongs to a run of right to left text
+    pub rtl: bool,
+    /// True if the glyph is drawn rotated 90 degrees clockwise, for text turned sideways in a
+    /// column of vertical text
+    pub sideways: bool,
+}
+
+impl ShapedGlyph {
+    /// Returns the distance the glyph moves the pen along its line, rightwards in horizontal
+    /// text and downwards in vertical text
+    pub fn line_advance(&self) -> f32 {
+        self.advance.0 - self.advance.1
+    }
+}
+
+/// The direction a run of text is shaped in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunDirection {
+    LeftToRight,
+    RightToLeft,
+    /// Down a column of vertical text, with glyphs upright
+    TopToBottom,
+}
+
+/// Turns runs of text into positioned glyphs using the font's OpenType layout tables
+///
+/// Shaping handles ligatures, contextual alternates, mark positioning and complex scripts,
+/// which can not be produced by looking up each character on its own.
+pub struct Shaper {
+    /// The face parsed once from `data`, which it borrows. It is declared first so it is dropped
+    /// before the data.
+    face: rustybuzz::Face<'static>,
+    /// Keeps the contents of the font file alive for as long as `face`
+    _data: FontData,
+}
+
+impl Shaper {
+    /// Creates a shaper for a font
+    /// # Arguments
+    /// * `data` - The contents of the font file
+    /// * `face_index` - Index of the face within the font file
+    pub fn new(data: FontData, face_index: u32) -> Result<Shaper, String> {
+        // Static data lives for the whole program, and shared data stays at the same address for
+        // as long as the shaper holds its reference to it, so the face can outlive the borrow
+        let bytes: &'static [u8] = unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) };
+
+        let face = match rustybuzz::Face::from_slice(bytes, face_index) {
+            Some(face) => face,
+            None => return Err(String::from("Font does not contain shaping tables")),
+        };
+
+        Ok(Shaper { face, _data: data })
+    }
+
+    /// Shapes a single run of text
+    ///
+    /// Control characters produce no glyphs. The glyphs are returned in visual order, so the
+    /// glyphs of right to left text start with its last character. Characters with a mirrored
+    /// form, such as brackets, are mirrored in right to left text.
+    ///
+    /// Text shaped top to bottom advances downwards using the font's vertical metrics (`vhea`
+    /// and `vmtx`), and is offset so each glyph is centered on the pen's horizontal position and
+    /// hangs below it. Its characters are replaced by their vertical forms through the `vert` and
+    /// `vrt2` features, such as the rotated brackets and long vowel mark of Japanese.
+    /// # Arguments
+    /// * `text` - The text to shape
+    /// * `features` - OpenType features to enable or disable, such as kerning
+    /// * `variations` - Axis values of a variable font, empty for the default design
+    /// * `font` - The font and size the glyphs will be drawn with
+    /// * `pixels_per_em` - Horizontal and vertical size of the em square in pixels
+    /// * `direction` - The direction to shape the text in
+    pub fn shape(
+        &self,
+        text: &str,
+        features: &[rustybuzz::Feature],
+        variations: &[Variation],
+        font: FontInstance,
+        pixels_per_em: (f32, f32),
+        direction: RunDirection,
+    ) -> Vec<ShapedGlyph> {
+        // Advances and positioning vary along with the glyph outlines, they are applied to a copy
+        // so the parsed face keeps the default design
+        let varied;
+        let face = if variations.is_empty() {
+            &self.face
+        } else {
+            let variations = variations
+                .iter()
+                .map(|variation| rustybuzz::Variation {
+                    tag: rustybuzz::Tag::from_bytes(&variation.tag),
+                    value: variation.value,
+                })
+                .collect::<Vec<_>>();
+
+            let mut face = self.face.clone();
+            face.set_variations(&variations);
+            varied = face;
+            &varied
+        };
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+
+        // The direction comes from the bidirectional algorithm and the writing mode rather than
+        // the script
+        buffer.set_direction(match direction {
+            RunDirection::LeftToRight => rustybuzz::Direction::LeftToRight,
+            RunDirection::RightToLeft => rustybuzz::Direction::RightToLeft,
+            RunDirection::TopToBottom => rustybuzz::Direction::TopToBottom,
+        });
+
+        let output = if direction == RunDirection::TopToBottom {
+            let mut features = features.to_vec();
+            features.push(feature(b"vert", true));
+            features.push(feature(b"vrt2", true));
+            rustybuzz::shape(face, &features, buffer)
+        } else {
+            rustybuzz::shape(face, features, buffer)
+        };
+        let rtl = direction == RunDirection::RightToLeft;
+
+        // Convert from font units to pixels
+        let scale_x = pixels_per_em.0 / face.units_per_em() as f32;
+        let scale_y = pixels_per_em.1 / face.units_per_em() as f32;
+
+        output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .filter(|(info, _)| !is_control(text, info.cluster as usize))
+            .map(|(info, position)| ShapedGlyph {
+                font,
+                glyph_index: info.glyph_id,
+                cluster: info.cluster as usize,
+                advance: (
+                    position.x_advance as f32 * scale_x,
+                    position.y_advance as f32 * scale_y,
+                ),
+                offset: (
+                    position.x_offset as f32 * scale_x,
+                    position.y_offset as f32 * scale_y,
+                ),
+                rtl,
+                sideways: false,
+            })
+            .collect()
+    }
+}
+
+/// Returns true if the character starting at a byte offset is a control character
+/// # Arguments
+/// * `text` - The text containing the character
+/// * `index` - Byte offset of the character
+pub(crate) fn is_control(text: &str, index: usize) -> bool {
+    match text[index..].chars().next() {
+        Some(c) => c.is_control(),
+        None => false,
+    }
+}
+
+/// Creates a feature setting that applies to the whole run
+/// # Arguments
+/// * `tag` - The four letter feature tag, such as `kern`
+/// * `enabled` - Whether the feature should be applied
+pub fn feature(tag: &[u8; 4], enabled: bool) -> rustybuzz::Feature {
+    rustybuzz::Feature::new(rustybuzz::Tag::from_bytes(tag), enabled as u32, ..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts;
+
+    fn shape(shaper: &Shaper, text: &str, direction: RunDirection) -> Vec<ShapedGlyph> {
+        let features = [feature(b"kern", true)];
+        shaper.shape(
+            text,
+            &features,
+            &[],
+            FontInstance::default(),
+            (32.0, 32.0),
+            direction,
+        )
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_a_font() {
+        assert!(Shaper::new(FontData::from(vec![0; 64]), 0).is_err());
+    }
+
+    #[test]
+    fn shapes_repeatedly_with_one_face() {
+        let shaper = Shaper::new(FontData::from(fonts::KOTTA_ONE.to_vec()), 0).unwrap();
+
+        let first = shape(&shaper, "Hello", RunDirection::LeftToRight);
+        let second = shape(&shaper, "Hello", RunDirection::LeftToRight);
+
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, second);
+        assert!(first.iter().all(|glyph| glyph.advance.0 > 0.0));
+    }
+
+    /// Pixeletter with a `wght` axis from 100 to 900 that widens every glyph by a quarter em
+    const PIXELETTER_WEIGHT: &[u8] = include_bytes!("fonts/PixeletterWeight.ttf");
+
+    #[test]
+    fn variations_change_advances_without_changing_the_face() {
+        let shaper = Shaper::new(FontData::from(PIXELETTER_WEIGHT), 0).unwrap();
+        let before = shape(&shaper, "Hello", RunDirection::LeftToRight);
+
+        let variations = [Variation {
+            tag: *b"wght",
+            value: 900.0,
+        }];
+        let bold = shaper.shape(
+            "Hello",
+            &[],
+            &variations,
+            FontInstance::default(),
+            (32.0, 32.0),
+            RunDirection::LeftToRight,
+        );
+
+        assert_eq!(bold.len(), before.len());
+        for (bold, regular) in bold.iter().zip(&before) {
+            assert_eq!(bold.advance.0, regular.advance.0 + 8.0);
+        }
+        assert_eq!(shape(&shaper, "Hello", RunDirection::LeftToRight), before);
+    }
+
+    #[test]
+    fn control_characters_produce_no_glyphs() {
+        let shaper = Shaper::new(FontData::from(fonts::KOTTA_ONE), 0).unwrap();
+
+        let glyphs = shape(&shaper, "a\tb", RunDirection::RightToLeft);
+
+        assert_eq!(
+            glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>(),
+            vec![2, 0]
+        );
+        assert!(glyphs.iter().all(|glyph| glyph.rtl));
+    }
+}
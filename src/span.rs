@@ -0,0 +1,279 @@
+use crate::color::Color;
+use crate::font::{Slant, Weight};
+use crate::outline::Outline;
+use crate::variation::Variation;
+
+use std::ops::Range;
+
+/// Lines drawn along a piece of text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Decoration {
+    /// Draw a line below the baseline
+    pub underline: bool,
+    /// Draw a line through the middle of the text
+    pub strikethrough: bool,
+}
+
+impl Decoration {
+    /// Returns true if no lines are drawn
+    pub fn is_none(&self) -> bool {
+        !self.underline && !self.strikethrough
+    }
+}
+
+/// Attributes applied to part of a piece of text, overriding the attributes of the text style
+///
+/// Attributes that are None are inherited from the style, or from earlier spans covering the
+/// same text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextSpan {
+    /// Byte range of the text the attributes apply to
+    pub range: Range<usize>,
+    /// Name of the font family
+    pub family: Option<String>,
+    /// The font size in points
+    pub size: Option<f32>,
+    pub weight: Option<Weight>,
+    pub slant: Option<Slant>,
+    pub color: Option<Color>,
+    pub decoration: Option<Decoration>,
+    /// Axis values of variable fonts, replacing the inherited values
+    pub variations: Option<Vec<Variation>>,
+    /// Stroke drawn around the glyphs, an outline with a width of 0 removes the inherited one
+    pub outline: Option<Outline>,
+}
+
+/// The attributes of a single character once every span covering it has been applied
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attributes<'a> {
+    /// Name of the font family, None for the renderer's font
+    pub family: Option<&'a str>,
+    /// The font size in points, None for the renderer's font size
+    pub size: Option<f32>,
+    pub weight: Weight,
+    pub slant: Slant,
+    pub color: Color,
+    pub decoration: Decoration,
+    /// Axis values of variable fonts
+    pub variations: &'a [Variation],
+    /// Stroke drawn around the glyphs, if any
+    pub outline: Option<Outline>,
+}
+
+/// Returns the attributes of the character at a byte offset
+///
+/// Spans are applied in order, so later spans take priority over earlier spans.
+/// # Arguments
+/// * `base` - The attributes of text not covered by a span
+/// * `spans` - Attributes applied to parts of the text
+/// * `index` - Byte offset of the character
+pub fn attributes_at<'a>(
+    base: Attributes<'a>,
+    spans: &'a [TextSpan],
+    index: usize,
+) -> Attributes<'a> {
+    let mut attributes = base;
+
+    for span in spans.iter().filter(|span| span.range.contains(&index)) {
+        if let Some(family) = &span.family {
+            attributes.family = Some(family);
+        }
+        if span.size.is_some() {
+            attributes.size = span.size;
+        }
+        if let Some(weight) = span.weight {
+            attributes.weight = weight;
+        }
+        if let Some(slant) = span.slant {
+            attributes.slant = slant;
+        }
+        if let Some(color) = span.color {
+            attributes.color = color;
+        }
+        if let Some(decoration) = span.decoration {
+            attributes.decoration = decoration;
+        }
+        if let Some(variations) = &span.variations {
+            attributes.variations = variations;
+        }
+        if let Some(outline) = span.outline {
+            attributes.outline = Some(outline).filter(|outline| outline.width > 0.0);
+        }
+    }
+
+    attributes
+}
+
+/// Splits a range of text into pieces that are each covered by the same spans
+/// # Arguments
+/// * `spans` - Attributes applied to parts of the text
+/// * `range` - Byte range of the text to split
+pub fn segments(spans: &[TextSpan], range: Range<usize>) -> Vec<Range<usize>> {
+    let mut boundaries = vec![range.start, range.end];
+    for span in spans.iter() {
+        for &boundary in [span.range.start, span.range.end].iter() {
+            if boundary > range.start && boundary < range.end {
+                boundaries.push(boundary);
+            }
+        }
+    }
+
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    boundaries.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color::rgb(1.0, 0.0, 0.0);
+    const BLUE: Color = Color::rgb(0.0, 0.0, 1.0);
+
+    /// Attributes of text without spans
+    fn base() -> Attributes<'static> {
+        Attributes {
+            family: None,
+            size: None,
+            weight: Weight::NORMAL,
+            slant: Slant::Normal,
+            color: Color::BLACK,
+            decoration: Decoration::default(),
+            variations: &[],
+            outline: None,
+        }
+    }
+
+    fn span(range: Range<usize>) -> TextSpan {
+        TextSpan {
+            range,
+            ..TextSpan::default()
+        }
+    }
+
+    #[test]
+    fn text_outside_of_spans_keeps_the_base_attributes() {
+        let spans = [TextSpan {
+            color: Some(RED),
+            ..span(2..4)
+        }];
+
+        assert_eq!(attributes_at(base(), &spans, 1), base());
+        assert_eq!(attributes_at(base(), &spans, 4), base());
+        assert_eq!(attributes_at(base(), &spans, 2).color, RED);
+    }
+
+    #[test]
+    fn later_spans_take_priority() {
+        let spans = [
+            TextSpan {
+                color: Some(RED),
+                weight: Some(Weight::BOLD),
+                ..span(0..10)
+            },
+            TextSpan {
+                color: Some(BLUE),
+                ..span(0..10)
+            },
+        ];
+
+        let attributes = attributes_at(base(), &spans, 5);
+
+        assert_eq!(attributes.color, BLUE);
+        assert_eq!(attributes.weight, Weight::BOLD);
+    }
+
+    #[test]
+    fn partially_overlapping_spans_combine_where_they_overlap() {
+        let spans = [
+            TextSpan {
+                family: Some(String::from("Kotta")),
+                ..span(0..6)
+            },
+            TextSpan {
+                size: Some(24.0),
+                slant: Some(Slant::Italic),
+                ..span(4..10)
+            },
+        ];
+
+        let first = attributes_at(base(), &spans, 2);
+        let both = attributes_at(base(), &spans, 5);
+        let second = attributes_at(base(), &spans, 8);
+
+        assert_eq!((first.family, first.size), (Some("Kotta"), None));
+        assert_eq!((both.family, both.size), (Some("Kotta"), Some(24.0)));
+        assert_eq!(both.slant, Slant::Italic);
+        assert_eq!((second.family, second.size), (None, Some(24.0)));
+    }
+
+    #[test]
+    fn outline_without_width_clears_an_inherited_outline() {
+        let spans = [
+            TextSpan {
+                outline: Some(Outline::new(2.0, RED)),
+                ..span(0..10)
+            },
+            TextSpan {
+                outline: Some(Outline::new(0.0, BLUE)),
+                ..span(3..6)
+            },
+        ];
+        let outlined = Attributes {
+            outline: Some(Outline::new(1.0, BLUE)),
+            ..base()
+        };
+
+        assert_eq!(
+            attributes_at(base(), &spans, 1).outline,
+            Some(Outline::new(2.0, RED))
+        );
+        assert_eq!(attributes_at(base(), &spans, 4).outline, None);
+        assert_eq!(attributes_at(outlined, &spans[1..], 4).outline, None);
+    }
+
+    #[test]
+    fn variations_replace_the_inherited_ones() {
+        let spans = [
+            TextSpan {
+                variations: Some(vec![
+                    Variation::new(b"wght", 700.0),
+                    Variation::new(b"wdth", 75.0),
+                ]),
+                ..span(0..10)
+            },
+            TextSpan {
+                variations: Some(vec![Variation::new(b"wght", 300.0)]),
+                ..span(5..10)
+            },
+        ];
+
+        assert_eq!(attributes_at(base(), &spans, 2).variations.len(), 2);
+        assert_eq!(
+            attributes_at(base(), &spans, 7).variations,
+            &[Variation::new(b"wght", 300.0)]
+        );
+    }
+
+    #[test]
+    fn segments_split_at_span_boundaries() {
+        let spans = [span(2..5), span(4..8)];
+
+        assert_eq!(segments(&spans, 0..10), vec![0..2, 2..4, 4..5, 5..8, 8..10]);
+    }
+
+    #[test]
+    fn segments_ignore_boundaries_outside_of_the_range() {
+        let spans = [span(0..3), span(7..12), span(20..30)];
+
+        assert_eq!(segments(&spans, 4..10), vec![4..7, 7..10]);
+        assert_eq!(segments(&spans, 3..7), vec![3..7]);
+    }
+
+    #[test]
+    fn segments_without_spans_cover_the_range() {
+        assert_eq!(segments(&[], 3..9), vec![3..9]);
+        assert!(segments(&[], 3..3).is_empty());
+    }
+}
@@ -0,0 +1,723 @@
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::color::Color;
+use crate::gl_util;
+use crate::hit_test;
+use crate::layout::{Alignment, Rect, TextLayout};
+use crate::renderer::{TextRenderer, TextStyle};
+use crate::vertical::WritingMode;
+
+use std::ops::Range;
+use std::time::Instant;
+
+/// Time the caret stays shown, then hidden, while blinking, in milliseconds
+const BLINK_INTERVAL: u128 = 530;
+
+/// Width of the caret in pixels
+const CARET_WIDTH: f32 = 2.0;
+
+/// Empty space kept between the text and the edges of the field, in pixels
+const PADDING: f32 = 4.0;
+
+/// An editable single line or multi line text field driven by SDL events
+///
+/// Text is typed through SDL `TextInput` events, which SDL sends while text input is started,
+/// the default on desktop platforms. The caret is moved with the arrow keys, Home and End, or by
+/// clicking. Holding Shift while moving the caret, or dragging the mouse, selects text. Holding
+/// Ctrl moves and deletes whole words, Ctrl+Home and Ctrl+End move to either end of the text and
+/// Ctrl+A selects everything.
+///
+/// A single line field scrolls horizontally to keep the caret in view. A multi line field wraps
+/// its text to its width and scrolls vertically instead.
+pub struct TextInput {
+    /// The area of the field, in the coordinates set up by `TextRenderer::set_projection`
+    pub bounds: Rect,
+    /// Color of the caret
+    pub caret_color: Color,
+    /// Color of the rectangles highlighting the selection
+    pub selection_color: Color,
+    style: TextStyle,
+    text: String,
+    multiline: bool,
+    /// Byte offset of the caret
+    caret: usize,
+    /// Byte offset of the other end of the selection, the same as the caret if nothing is
+    /// selected
+    anchor: usize,
+    /// How far the text is scrolled left and up, in pixels
+    scroll: (f32, f32),
+    focused: bool,
+    dragging: bool,
+    /// When the caret last moved, it starts blinking from then
+    moved: Instant,
+}
+
+impl TextInput {
+    /// Creates an empty field
+    ///
+    /// The field always lays text out in horizontal lines aligned to the start, the style's
+    /// alignment, writing mode and maximum width are replaced.
+    /// # Arguments
+    /// * `bounds` - The area of the field
+    /// * `style` - The style to draw the text with
+    /// * `multiline` - Whether the text can span several lines, line breaks are removed otherwise
+    pub fn new(bounds: Rect, style: TextStyle, multiline: bool) -> TextInput {
+        TextInput {
+            bounds,
+            caret_color: style.color,
+            selection_color: Color::rgba(0.2, 0.4, 1.0, 0.5),
+            style: TextStyle {
+                align: Alignment::Start,
+                writing_mode: WritingMode::Horizontal,
+                max_width: None,
+                ..style
+            },
+            text: String::new(),
+            multiline,
+            caret: 0,
+            anchor: 0,
+            scroll: (0.0, 0.0),
+            focused: false,
+            dragging: false,
+            moved: Instant::now(),
+        }
+    }
+
+    /// Returns the text in the field
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text in the field, placing the caret at its end
+    /// # Arguments
+    /// * `text` - The new text
+    pub fn set_text(&mut self, text: &str) {
+        self.text = self.filter(text);
+        self.caret = self.text.len();
+        self.anchor = self.caret;
+        self.moved = Instant::now();
+    }
+
+    /// Returns the style the text is drawn with
+    pub fn style(&self) -> &TextStyle {
+        &self.style
+    }
+
+    /// Returns the byte offset of the caret
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Returns the byte range of the selected text, empty if nothing is selected
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    /// Returns the selected text
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection()]
+    }
+
+    /// Returns true if the field receives key presses and typed text
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Gives the field focus or takes it away, fields are focused by clicking on them
+    /// # Arguments
+    /// * `focused` - Whether the field receives key presses and typed text
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.dragging = false;
+        self.moved = Instant::now();
+    }
+
+    /// Updates the field from an SDL event
+    ///
+    /// Returns true if the event was used by the field, either to edit it or because the field
+    /// was clicked.
+    /// # Arguments
+    /// * `event` - The event to handle
+    /// * `renderer` - The renderer the field is drawn with, used to find the text under the mouse
+    /// * `window_size` - Width and height of the window, as passed to `set_projection`
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        renderer: &mut TextRenderer,
+        window_size: (u32, u32),
+    ) -> bool {
+        match event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                let point = hit_test::window_to_view((*x, *y), window_size);
+                if !self.bounds.contains(point) {
+                    self.set_focused(false);
+                    return false;
+                }
+
+                self.set_focused(true);
+                self.dragging = true;
+
+                let index = self.index_at(renderer, point);
+                self.move_caret(index, false);
+                true
+            }
+            Event::MouseMotion { x, y, .. } if self.dragging => {
+                // Dragging past the edge of the field scrolls it
+                let point = hit_test::window_to_view((*x, *y), window_size);
+                let index = self.index_at(renderer, point);
+                self.move_caret(index, true);
+                self.scroll_to_caret(renderer);
+                true
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } if self.dragging => {
+                self.dragging = false;
+                true
+            }
+            Event::TextInput { text, .. } if self.focused => {
+                self.insert(text);
+                self.scroll_to_caret(renderer);
+                true
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } if self.focused => self.key_down(renderer, *keycode, *keymod),
+            _ => false,
+        }
+    }
+
+    /// Draws the field immediately, along with any previously queued text
+    ///
+    /// The field's text is clipped to its bounds. The selection is highlighted behind the text
+    /// and the caret blinks while the field is focused. The projection must match the size of
+    /// the viewport, as it does when `set_projection` is given the window size.
+    /// # Arguments
+    /// * `renderer` - The renderer to draw with
+    pub fn draw(&mut self, renderer: &mut TextRenderer) {
+        // Text queued before the field is not clipped
+        renderer.flush();
+
+        let (layout, origin) = self.layout(renderer);
+
+        for rect in hit_test::range_rects(&self.text, &layout, self.selection()) {
+            renderer.queue_rect(&rect.translated(origin), self.selection_color);
+        }
+
+        // The caret is shown for the first interval after it moves, then every other interval
+        let blink = self.moved.elapsed().as_millis() / BLINK_INTERVAL;
+        if self.focused && blink % 2 == 0 {
+            if let Some(rect) = hit_test::caret_rect(&self.text, &layout, self.caret, CARET_WIDTH) {
+                renderer.queue_rect(&rect.translated(origin), self.caret_color);
+            }
+        }
+
+        renderer.queue_layout(&layout, origin, &self.style);
+
+        let previous = gl_util::scissor();
+        gl_util::set_scissor(Some(scissor_box(&self.bounds)));
+        renderer.flush();
+        gl_util::set_scissor(previous);
+    }
+
+    /// Handles a key press while the field is focused, returns true if the key was used
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    /// * `keycode` - The key pressed
+    /// * `keymod` - The modifier keys held
+    fn key_down(&mut self, renderer: &mut TextRenderer, keycode: Keycode, keymod: Mod) -> bool {
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+
+        match keycode {
+            Keycode::Left | Keycode::Right => {
+                self.step_caret(keycode == Keycode::Right, ctrl, shift);
+            }
+            Keycode::Up | Keycode::Down => {
+                let index = self.line_above_or_below(renderer, keycode == Keycode::Up);
+                self.move_caret(index, shift);
+            }
+            Keycode::Home | Keycode::End => {
+                let home = keycode == Keycode::Home;
+                let index = match (ctrl || !self.multiline, home) {
+                    (true, true) => 0,
+                    (true, false) => self.text.len(),
+                    (false, _) => {
+                        let line = self.caret_line(renderer);
+                        if home {
+                            line.start
+                        } else {
+                            line.end
+                        }
+                    }
+                };
+                self.move_caret(index, shift);
+            }
+            Keycode::Backspace | Keycode::Delete => self.delete(keycode == Keycode::Delete, ctrl),
+            Keycode::Return | Keycode::KpEnter if self.multiline => self.insert("\n"),
+            Keycode::A if ctrl => self.select_all(),
+            _ => return false,
+        }
+
+        self.scroll_to_caret(renderer);
+        true
+    }
+
+    /// Moves the caret one grapheme cluster or word to the left or right
+    ///
+    /// Moving without extending the selection collapses an existing selection to the side
+    /// being moved towards.
+    /// # Arguments
+    /// * `forward` - Whether to move towards the end of the text
+    /// * `word` - Whether to move by whole words
+    /// * `select` - Whether to extend the selection
+    fn step_caret(&mut self, forward: bool, word: bool, select: bool) {
+        let selection = self.selection();
+
+        let index = match (select || selection.is_empty(), forward) {
+            (true, _) => step(&self.text, self.caret, forward, word),
+            (false, true) => selection.end,
+            (false, false) => selection.start,
+        };
+        self.move_caret(index, select);
+    }
+
+    /// Deletes the selection, or the grapheme cluster or word beside the caret if nothing is
+    /// selected
+    /// # Arguments
+    /// * `forward` - Whether to delete after the caret rather than before it
+    /// * `word` - Whether to delete a whole word
+    fn delete(&mut self, forward: bool, word: bool) {
+        if self.anchor == self.caret {
+            self.anchor = step(&self.text, self.caret, forward, word);
+        }
+        self.insert("");
+    }
+
+    /// Selects all of the text, leaving the caret at its end
+    fn select_all(&mut self) {
+        self.anchor = 0;
+        self.caret = self.text.len();
+        self.moved = Instant::now();
+    }
+
+    /// Replaces the selection with text, leaving the caret after it
+    /// # Arguments
+    /// * `text` - The text to insert
+    fn insert(&mut self, text: &str) {
+        let selection = self.selection();
+        let text = self.filter(text);
+
+        self.text.replace_range(selection.clone(), &text);
+        self.caret = selection.start + text.len();
+        self.anchor = self.caret;
+        self.moved = Instant::now();
+    }
+
+    /// Returns text with line breaks removed if the field is a single line
+    /// # Arguments
+    /// * `text` - The text to filter
+    fn filter(&self, text: &str) -> String {
+        if self.multiline {
+            String::from(text)
+        } else {
+            text.chars().filter(|&c| c != '\n' && c != '\r').collect()
+        }
+    }
+
+    /// Moves the caret, restarting its blinking
+    /// # Arguments
+    /// * `index` - Byte offset to move the caret to
+    /// * `select` - Whether to select the text passed over, otherwise the selection is cleared
+    fn move_caret(&mut self, index: usize, select: bool) {
+        self.caret = index;
+        if !select {
+            self.anchor = index;
+        }
+        self.moved = Instant::now();
+    }
+
+    /// Lays out the field's text and returns it along with the position of the layout origin
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    fn layout(&self, renderer: &mut TextRenderer) -> (TextLayout, (f32, f32)) {
+        if self.multiline {
+            let width = (self.bounds.width - PADDING * 2.0).max(0.0);
+            let layout = renderer.layout_in_box(&self.text, &self.style, width);
+
+            // The first line touches the top of the field when not scrolled
+            let ascender = layout.bounds.y + layout.bounds.height;
+            let top = self.bounds.y + self.bounds.height - PADDING;
+            let origin = (self.bounds.x + PADDING, top - ascender + self.scroll.1);
+
+            (layout, origin)
+        } else {
+            let layout = renderer.layout(&self.text, &self.style);
+
+            // Center the line vertically
+            let (ascender, descender) = match layout.lines.first() {
+                Some(line) => (line.ascender, line.descender),
+                None => (0.0, 0.0),
+            };
+            let origin = (
+                self.bounds.x + PADDING - self.scroll.0,
+                self.bounds.y + (self.bounds.height - ascender - descender) / 2.0,
+            );
+
+            (layout, origin)
+        }
+    }
+
+    /// Returns the caret position closest to a point
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    /// * `point` - The point, in the same coordinates as the field's bounds
+    fn index_at(&self, renderer: &mut TextRenderer, point: (f32, f32)) -> usize {
+        let (layout, origin) = self.layout(renderer);
+        let point = (point.0 - origin.0, point.1 - origin.1);
+
+        match hit_test::hit_test(&self.text, &layout, point) {
+            Some(hit) => hit.caret_index(),
+            None => self.text.len(),
+        }
+    }
+
+    /// Returns the byte range of the line the caret is on
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    fn caret_line(&self, renderer: &mut TextRenderer) -> Range<usize> {
+        let (layout, _) = self.layout(renderer);
+
+        match hit_test::line_at(&layout, self.caret) {
+            Some(line) => layout.lines[line].range.clone(),
+            None => self.caret..self.caret,
+        }
+    }
+
+    /// Returns the caret position on the line above or below the caret, closest to the caret
+    /// horizontally
+    ///
+    /// Moving up from the first line goes to the start of the text and moving down from the
+    /// last line goes to its end.
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    /// * `up` - Whether to move to the line above
+    fn line_above_or_below(&self, renderer: &mut TextRenderer, up: bool) -> usize {
+        let (layout, _) = self.layout(renderer);
+
+        let line = hit_test::line_at(&layout, self.caret);
+        let caret = hit_test::caret_rect(&self.text, &layout, self.caret, CARET_WIDTH);
+        let (line, caret) = match (line, caret) {
+            (Some(line), Some(caret)) => (line, caret),
+            _ => return self.caret,
+        };
+
+        let target = if up {
+            line.checked_sub(1)
+        } else {
+            Some(line + 1).filter(|&line| line < layout.lines.len())
+        };
+        let target = match target {
+            Some(target) => &layout.lines[target],
+            None if up => return 0,
+            None => return self.text.len(),
+        };
+
+        let point = (
+            caret.x + caret.width / 2.0,
+            target.baseline + (target.ascender + target.descender) / 2.0,
+        );
+        match hit_test::hit_test(&self.text, &layout, point) {
+            Some(hit) => hit.caret_index(),
+            None => self.caret,
+        }
+    }
+
+    /// Scrolls the field as little as possible to bring the caret into view
+    /// # Arguments
+    /// * `renderer` - The renderer the field is drawn with
+    fn scroll_to_caret(&mut self, renderer: &mut TextRenderer) {
+        let (layout, origin) = self.layout(renderer);
+        let caret = match hit_test::caret_rect(&self.text, &layout, self.caret, CARET_WIDTH) {
+            Some(rect) => rect.translated(origin),
+            None => return,
+        };
+
+        if self.multiline {
+            let top = self.bounds.y + self.bounds.height - PADDING;
+            let bottom = self.bounds.y + PADDING;
+
+            if caret.y + caret.height > top {
+                self.scroll.1 -= caret.y + caret.height - top;
+            } else if caret.y < bottom {
+                self.scroll.1 += bottom - caret.y;
+            }
+
+            // Never scroll above the first line
+            self.scroll.1 = self.scroll.1.max(0.0);
+        } else {
+            let left = self.bounds.x + PADDING;
+            let right = self.bounds.x + self.bounds.width - PADDING;
+
+            if caret.x < left {
+                self.scroll.0 -= left - caret.x;
+            } else if caret.x + caret.width > right {
+                self.scroll.0 += caret.x + caret.width - right;
+            }
+
+            // Do not leave empty space after the end of the text, or scroll past its start
+            let end = layout.bounds.x + layout.bounds.width + CARET_WIDTH;
+            self.scroll.0 = self.scroll.0.min(end - (right - left)).max(0.0);
+        }
+    }
+}
+
+/// Returns the box of the framebuffer covered by a rectangle, in pixels from its bottom left
+/// corner
+/// # Arguments
+/// * `rect` - The rectangle, in the coordinates set up by `TextRenderer::set_projection`
+fn scissor_box(rect: &Rect) -> [i32; 4] {
+    let viewport = gl_util::viewport();
+
+    // The projection puts the origin at the center of the viewport
+    let left = viewport[0] as f32 + viewport[2] as f32 / 2.0 + rect.x;
+    let bottom = viewport[1] as f32 + viewport[3] as f32 / 2.0 + rect.y;
+
+    [
+        left.floor() as i32,
+        bottom.floor() as i32,
+        rect.width.max(0.0).ceil() as i32,
+        rect.height.max(0.0).ceil() as i32,
+    ]
+}
+
+/// Returns the byte offset one grapheme cluster or word away from a position
+/// # Arguments
+/// * `text` - The text
+/// * `index` - Byte offset of the position
+/// * `forward` - Whether to step towards the end of the text
+/// * `word` - Whether to step over a whole word
+fn step(text: &str, index: usize, forward: bool, word: bool) -> usize {
+    match (forward, word) {
+        (false, true) => previous_word(text, index),
+        (false, false) => previous_grapheme(text, index),
+        (true, true) => next_word(text, index),
+        (true, false) => next_grapheme(text, index),
+    }
+}
+
+/// Returns the byte offset of the grapheme cluster before a position
+/// # Arguments
+/// * `text` - The text
+/// * `index` - Byte offset of the position
+fn previous_grapheme(text: &str, index: usize) -> usize {
+    match text[..index].graphemes(true).next_back() {
+        Some(grapheme) => index - grapheme.len(),
+        None => index,
+    }
+}
+
+/// Returns the byte offset of the end of the grapheme cluster after a position
+/// # Arguments
+/// * `text` - The text
+/// * `index` - Byte offset of the position
+fn next_grapheme(text: &str, index: usize) -> usize {
+    match text[index..].graphemes(true).next() {
+        Some(grapheme) => index + grapheme.len(),
+        None => index,
+    }
+}
+
+/// Returns the byte offset of the start of the word before a position, skipping any spaces and
+/// punctuation in between
+/// # Arguments
+/// * `text` - The text
+/// * `index` - Byte offset of the position
+fn previous_word(text: &str, index: usize) -> usize {
+    text.unicode_word_indices()
+        .map(|(start, _)| start)
+        .take_while(|&start| start < index)
+        .last()
+        .unwrap_or(0)
+}
+
+/// Returns the byte offset of the end of the word after a position, skipping any spaces and
+/// punctuation in between
+/// # Arguments
+/// * `text` - The text
+/// * `index` - Byte offset of the position
+fn next_word(text: &str, index: usize) -> usize {
+    text.unicode_word_indices()
+        .map(|(start, word)| start + word.len())
+        .find(|&end| end > index)
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_field(text: &str, multiline: bool) -> TextInput {
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 20.0,
+        };
+        let mut field = TextInput::new(bounds, TextStyle::default(), multiline);
+        field.set_text(text);
+        field
+    }
+
+    #[test]
+    fn steps_between_graphemes() {
+        // An accented letter of three bytes followed by a letter
+        let text = "e\u{301}x";
+
+        assert_eq!(next_grapheme(text, 0), 3);
+        assert_eq!(next_grapheme(text, 3), 4);
+        assert_eq!(next_grapheme(text, 4), 4);
+        assert_eq!(previous_grapheme(text, 4), 3);
+        assert_eq!(previous_grapheme(text, 3), 0);
+        assert_eq!(previous_grapheme(text, 0), 0);
+    }
+
+    #[test]
+    fn steps_between_words() {
+        let text = "hello, big world";
+
+        assert_eq!(next_word(text, 0), 5);
+        assert_eq!(next_word(text, 5), 10);
+        assert_eq!(next_word(text, 7), 10);
+        assert_eq!(next_word(text, 16), 16);
+        assert_eq!(previous_word(text, 16), 11);
+        assert_eq!(previous_word(text, 11), 7);
+        assert_eq!(previous_word(text, 9), 7);
+        assert_eq!(previous_word(text, 3), 0);
+        assert_eq!(previous_word(text, 0), 0);
+    }
+
+    #[test]
+    fn steps_over_trailing_punctuation() {
+        assert_eq!(next_word("end.  ", 3), 6);
+        assert_eq!(previous_word("  .start", 2), 0);
+    }
+
+    #[test]
+    fn step_picks_direction_and_unit() {
+        let text = "ab cd";
+
+        assert_eq!(step(text, 3, true, false), 4);
+        assert_eq!(step(text, 3, true, true), 5);
+        assert_eq!(step(text, 4, false, false), 3);
+        assert_eq!(step(text, 4, false, true), 3);
+    }
+
+    #[test]
+    fn moving_collapses_selection() {
+        let mut field = new_field("hello world", false);
+        field.select_all();
+        assert_eq!(field.selected_text(), "hello world");
+
+        field.step_caret(false, false, false);
+        assert_eq!(field.caret(), 0);
+        assert!(field.selection().is_empty());
+
+        field.select_all();
+        field.step_caret(true, false, false);
+        assert_eq!(field.caret(), 11);
+        assert!(field.selection().is_empty());
+    }
+
+    #[test]
+    fn moving_with_shift_extends_selection() {
+        let mut field = new_field("hello world", false);
+        field.move_caret(0, false);
+
+        field.step_caret(true, true, true);
+        field.step_caret(true, false, true);
+        assert_eq!(field.selection(), 0..6);
+
+        field.step_caret(false, false, true);
+        assert_eq!(field.selected_text(), "hello");
+    }
+
+    #[test]
+    fn backspace_and_delete_graphemes() {
+        let mut field = new_field("ae\u{301}b", false);
+        field.move_caret(4, false);
+
+        field.delete(false, false);
+        assert_eq!(field.text(), "ab");
+        assert_eq!(field.caret(), 1);
+
+        field.delete(true, false);
+        assert_eq!(field.text(), "a");
+        assert_eq!(field.caret(), 1);
+
+        // Nothing to delete at the end
+        field.delete(true, false);
+        assert_eq!(field.text(), "a");
+    }
+
+    #[test]
+    fn backspace_and_delete_words() {
+        let mut field = new_field("one two three", false);
+        field.move_caret(7, false);
+
+        field.delete(false, true);
+        assert_eq!(field.text(), "one  three");
+        assert_eq!(field.caret(), 4);
+
+        field.delete(true, true);
+        assert_eq!(field.text(), "one ");
+        assert_eq!(field.caret(), 4);
+    }
+
+    #[test]
+    fn deleting_removes_selection_only() {
+        let mut field = new_field("hello world", false);
+        field.move_caret(2, false);
+        field.move_caret(8, true);
+
+        field.delete(false, true);
+        assert_eq!(field.text(), "herld");
+        assert_eq!(field.caret(), 2);
+        assert!(field.selection().is_empty());
+    }
+
+    #[test]
+    fn typing_replaces_selection() {
+        let mut field = new_field("hello world", false);
+        field.move_caret(11, false);
+        field.move_caret(6, true);
+
+        field.insert("there");
+        assert_eq!(field.text(), "hello there");
+        assert_eq!(field.caret(), 11);
+    }
+
+    #[test]
+    fn single_line_removes_line_breaks() {
+        let mut field = new_field("a\r\nb", false);
+        assert_eq!(field.text(), "ab");
+
+        field.insert("\nc");
+        assert_eq!(field.text(), "abc");
+
+        let mut field = new_field("a\nb", true);
+        field.insert("\n");
+        assert_eq!(field.text(), "a\nb\n");
+    }
+}
@@ -0,0 +1,360 @@
+use crate::font_db;
+
+use rustybuzz::ttf_parser;
+
+/// Identifies a set of variation axis values used with a font, 0 is the font's default design
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VariationId(pub u32);
+
+/// A value for one of a variable font's design axes, like the CSS `font-variation-settings`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Variation {
+    /// The four letter axis tag, such as `wght` or `opsz`
+    pub tag: [u8; 4],
+    pub value: f32,
+}
+
+impl Variation {
+    /// Creates an axis value
+    /// # Arguments
+    /// * `tag` - The four letter axis tag, such as `wght`
+    /// * `value` - The value of the axis, in the axis' own units
+    pub fn new(tag: &[u8; 4], value: f32) -> Variation {
+        Variation { tag: *tag, value }
+    }
+}
+
+/// A design axis of a variable font, such as weight or width
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariationAxis {
+    /// The four letter axis tag, such as `wght`
+    pub tag: [u8; 4],
+    /// Name of the axis from the font's name table
+    pub name: String,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+    /// Whether the font asks for the axis not to be shown in user interfaces
+    pub hidden: bool,
+}
+
+/// A predefined set of axis values of a variable font, such as "Bold Condensed"
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedInstance {
+    /// Name of the instance from the font's name table
+    pub name: String,
+    /// The value of every axis of the font
+    pub coordinates: Vec<Variation>,
+}
+
+/// Reads the design axes and named instances of a variable font
+///
+/// Fonts without an `fvar` table have neither.
+/// # Arguments
+/// * `face` - The parsed face
+pub(crate) fn read_axes(face: &ttf_parser::Face) -> (Vec<VariationAxis>, Vec<NamedInstance>) {
+    let table = match face.raw_face().table(ttf_parser::Tag::from_bytes(b"fvar")) {
+        Some(table) => table,
+        None => return (vec![], vec![]),
+    };
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *table.get(offset)?,
+            *table.get(offset + 1)?,
+        ]))
+    };
+    let read_fixed = |offset: usize| -> Option<f32> {
+        let value = i32::from_be_bytes([
+            *table.get(offset)?,
+            *table.get(offset + 1)?,
+            *table.get(offset + 2)?,
+            *table.get(offset + 3)?,
+        ]);
+        Some(value as f32 / 65536.0)
+    };
+
+    let header = (
+        read_u16(4),
+        read_u16(8),
+        read_u16(10),
+        read_u16(12),
+        read_u16(14),
+    );
+    let (axes_offset, axis_count, axis_size, instance_count, instance_size) = match header {
+        (Some(a), Some(b), Some(c), Some(d), Some(e)) => {
+            (a as usize, b as usize, c as usize, d as usize, e as usize)
+        }
+        _ => return (vec![], vec![]),
+    };
+
+    let mut axes = vec![];
+    for i in 0..axis_count {
+        let offset = axes_offset + i * axis_size;
+        let axis = (|| {
+            let tag = table.get(offset..offset + 4)?;
+            let name_id = read_u16(offset + 18)?;
+
+            Some(VariationAxis {
+                tag: [tag[0], tag[1], tag[2], tag[3]],
+                name: font_db::face_name(face, name_id)
+                    .unwrap_or_else(|| String::from_utf8_lossy(tag).into_owned()),
+                min: read_fixed(offset + 4)?,
+                default: read_fixed(offset + 8)?,
+                max: read_fixed(offset + 12)?,
+                hidden: read_u16(offset + 16)? & 0x0001 != 0,
+            })
+        })();
+
+        match axis {
+            Some(axis) => axes.push(axis),
+            None => return (vec![], vec![]),
+        }
+    }
+
+    // Instances directly follow the axes
+    let mut instances = vec![];
+    let instances_offset = axes_offset + axis_count * axis_size;
+    for i in 0..instance_count {
+        let offset = instances_offset + i * instance_size;
+        let instance = (|| {
+            let name_id = read_u16(offset)?;
+
+            let mut coordinates = vec![];
+            for (j, axis) in axes.iter().enumerate() {
+                coordinates.push(Variation {
+                    tag: axis.tag,
+                    value: read_fixed(offset + 4 + j * 4)?,
+                });
+            }
+
+            Some(NamedInstance {
+                name: font_db::face_name(face, name_id).unwrap_or_default(),
+                coordinates,
+            })
+        })();
+
+        if let Some(instance) = instance {
+            instances.push(instance);
+        }
+    }
+
+    (axes, instances)
+}
+
+/// Returns the value of every axis of a font in the font's axis order
+///
+/// Axes without a setting keep their default value, values outside of an axis' range are
+/// clamped to it and settings for axes the font does not have are ignored. When an axis is set
+/// more than once the last setting is used.
+/// # Arguments
+/// * `axes` - The design axes of the font
+/// * `settings` - The requested axis values
+pub fn resolve(axes: &[VariationAxis], settings: &[Variation]) -> Vec<Variation> {
+    axes.iter()
+        .map(|axis| {
+            let value = settings
+                .iter()
+                .rev()
+                .find(|setting| setting.tag == axis.tag)
+                .map(|setting| setting.value.clamp(axis.min, axis.max))
+                .unwrap_or(axis.default);
+
+            Variation {
+                tag: axis.tag,
+                value,
+            }
+        })
+        .collect()
+}
+
+/// Rounds an axis value to a step of one design unit, or a hundredth of the axis' range for
+/// narrower axes, so nearby values share a design
+///
+/// Steps are counted from the axis' default, which is always kept exactly.
+/// # Arguments
+/// * `axis` - The design axis the value is for
+/// * `value` - The value to round, in the axis' own units
+pub(crate) fn quantize(axis: &VariationAxis, value: f32) -> f32 {
+    let step = ((axis.max - axis.min) / 100.0).min(1.0);
+    if step <= 0.0 {
+        return axis.default;
+    }
+
+    let steps = ((value - axis.default) / step).round();
+    (axis.default + steps * step).clamp(axis.min, axis.max)
+}
+
+/// Converts a value to 16.16 fixed point, the precision Freetype and OpenType store axis values in
+/// # Arguments
+/// * `value` - The value to convert
+pub(crate) fn to_fixed(value: f32) -> i32 {
+    (value * 65536.0).round() as i32
+}
+
+/// Sets the design coordinates glyphs are loaded from a Freetype face with
+/// # Arguments
+/// * `face` - The face to change
+/// * `coordinates` - The value of every axis of the face in 16.16 fixed point
+pub(crate) fn set_coordinates(
+    face: &mut freetype::Face,
+    coordinates: &[i32],
+) -> Result<(), String> {
+    use freetype::ffi;
+
+    extern "C" {
+        fn FT_Set_Var_Design_Coordinates(
+            face: ffi::FT_Face,
+            num_coords: ffi::FT_UInt,
+            coords: *mut ffi::FT_Fixed,
+        ) -> ffi::FT_Error;
+    }
+
+    let mut coordinates = coordinates
+        .iter()
+        .map(|&coordinate| coordinate as ffi::FT_Fixed)
+        .collect::<Vec<_>>();
+
+    let error = unsafe {
+        FT_Set_Var_Design_Coordinates(
+            face.raw_mut(),
+            coordinates.len() as ffi::FT_UInt,
+            coordinates.as_mut_ptr(),
+        )
+    };
+
+    if error == ffi::FT_Err_Ok {
+        Ok(())
+    } else {
+        Err(format!("Unable to set font variation: error {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fonts;
+
+    /// Pixeletter with a `wght` axis from 100 to 900 and an instance at 900
+    const PIXELETTER_WEIGHT: &[u8] = include_bytes!("fonts/PixeletterWeight.ttf");
+
+    fn axis(tag: &[u8; 4], min: f32, default: f32, max: f32) -> VariationAxis {
+        VariationAxis {
+            tag: *tag,
+            name: String::new(),
+            min,
+            default,
+            max,
+            hidden: false,
+        }
+    }
+
+    fn values(variations: &[Variation]) -> Vec<f32> {
+        variations.iter().map(|variation| variation.value).collect()
+    }
+
+    #[test]
+    fn resolve_uses_defaults_for_unset_axes() {
+        let axes = [
+            axis(b"wght", 100.0, 400.0, 900.0),
+            axis(b"wdth", 50.0, 100.0, 200.0),
+        ];
+
+        let resolved = resolve(&axes, &[Variation::new(b"wdth", 75.0)]);
+
+        assert_eq!(resolved[0].tag, *b"wght");
+        assert_eq!(resolved[1].tag, *b"wdth");
+        assert_eq!(values(&resolved), vec![400.0, 75.0]);
+    }
+
+    #[test]
+    fn resolve_clamps_to_the_axis_range() {
+        let axes = [axis(b"wght", 100.0, 400.0, 900.0)];
+
+        assert_eq!(
+            values(&resolve(&axes, &[Variation::new(b"wght", 1000.0)])),
+            vec![900.0]
+        );
+        assert_eq!(
+            values(&resolve(&axes, &[Variation::new(b"wght", 0.0)])),
+            vec![100.0]
+        );
+    }
+
+    #[test]
+    fn resolve_uses_the_last_setting_of_an_axis() {
+        let axes = [axis(b"wght", 100.0, 400.0, 900.0)];
+        let settings = [
+            Variation::new(b"wght", 700.0),
+            Variation::new(b"wght", 300.0),
+        ];
+
+        assert_eq!(values(&resolve(&axes, &settings)), vec![300.0]);
+    }
+
+    #[test]
+    fn resolve_ignores_unknown_axes() {
+        let axes = [axis(b"wght", 100.0, 400.0, 900.0)];
+        let settings = [Variation::new(b"slnt", -10.0)];
+
+        assert_eq!(values(&resolve(&axes, &settings)), vec![400.0]);
+        assert!(resolve(&[], &settings).is_empty());
+    }
+
+    #[test]
+    fn quantize_rounds_to_whole_design_units() {
+        let weight = axis(b"wght", 100.0, 400.0, 900.0);
+
+        assert_eq!(quantize(&weight, 400.2), 400.0);
+        assert_eq!(quantize(&weight, 612.7), 613.0);
+        assert_eq!(quantize(&weight, 900.0), 900.0);
+    }
+
+    #[test]
+    fn quantize_uses_finer_steps_on_narrow_axes() {
+        let italic = axis(b"ital", 0.0, 0.0, 1.0);
+
+        assert!((quantize(&italic, 0.504) - 0.5).abs() < 1e-6);
+        assert_eq!(quantize(&italic, 1.0), 1.0);
+        assert_eq!(quantize(&italic, 0.001), 0.0);
+    }
+
+    #[test]
+    fn quantize_counts_steps_from_the_default() {
+        let size = axis(b"opsz", 8.0, 12.3, 72.0);
+
+        assert_eq!(quantize(&size, 12.3), 12.3);
+        assert_eq!(quantize(&size, 12.4), 12.3);
+        assert_eq!(quantize(&axis(b"wght", 400.0, 400.0, 400.0), 500.0), 400.0);
+    }
+
+    #[test]
+    fn reads_axes_and_instances() {
+        let face = ttf_parser::Face::parse(PIXELETTER_WEIGHT, 0).unwrap();
+
+        let (axes, instances) = read_axes(&face);
+
+        // Neither the axis nor the instance is named in the font's name table
+        assert_eq!(
+            axes,
+            vec![VariationAxis {
+                name: String::from("wght"),
+                ..axis(b"wght", 100.0, 400.0, 900.0)
+            }]
+        );
+        assert_eq!(
+            instances,
+            vec![NamedInstance {
+                name: String::new(),
+                coordinates: vec![Variation::new(b"wght", 900.0)],
+            }]
+        );
+    }
+
+    #[test]
+    fn fonts_without_fvar_have_no_axes() {
+        let face = ttf_parser::Face::parse(fonts::PIXELETTER, 0).unwrap();
+
+        assert_eq!(read_axes(&face), (vec![], vec![]));
+    }
+}
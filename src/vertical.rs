@@ -0,0 +1,195 @@
+use crate::layout::FontMetrics;
+use crate::shaping::ShapedGlyph;
+
+/// The direction lines of text are written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WritingMode {
+    /// Lines run left to right and are stacked from top to bottom
+    #[default]
+    Horizontal,
+    /// Lines run top to bottom as columns, stacked from right to left like Japanese and Chinese
+    /// text set vertically
+    ///
+    /// Characters of scripts written vertically are drawn upright, other text such as Latin is
+    /// turned sideways, rotated 90 degrees clockwise.
+    Vertical,
+}
+
+/// Ranges of characters drawn upright in vertical text, in increasing order
+///
+/// Simplified from the Vertical_Orientation property (UAX #50): characters it orients upright,
+/// or upright with a vertical alternate supplied by the font's `vert` feature. Everything else
+/// is turned sideways.
+const UPRIGHT: [(u32, u32); 43] = [
+    (0x00A7, 0x00A7),
+    (0x00A9, 0x00A9),
+    (0x00AE, 0x00AE),
+    (0x00B1, 0x00B1),
+    (0x00BC, 0x00BE),
+    (0x00D7, 0x00D7),
+    (0x00F7, 0x00F7),
+    (0x02EA, 0x02EB),
+    (0x1100, 0x11FF),
+    (0x1400, 0x167F),
+    (0x18B0, 0x18FF),
+    (0x2016, 0x2016),
+    (0x2020, 0x2021),
+    (0x2030, 0x2031),
+    (0x203B, 0x203C),
+    (0x2047, 0x2049),
+    (0x2100, 0x2189),
+    (0x221E, 0x221E),
+    (0x2234, 0x2235),
+    (0x2300, 0x2307),
+    (0x230C, 0x231F),
+    (0x2324, 0x232B),
+    (0x237D, 0x239A),
+    (0x23BE, 0x23FF),
+    (0x2400, 0x2422),
+    (0x2424, 0x24FF),
+    (0x25A0, 0x2619),
+    (0x2620, 0x2767),
+    (0x2776, 0x2793),
+    (0x2B12, 0x2B2F),
+    (0x2B50, 0x2B59),
+    (0x2E80, 0x2FFF),
+    (0x3000, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7FF),
+    (0xE000, 0xFAFF),
+    (0xFE10, 0xFE1F),
+    (0xFE30, 0xFE6F),
+    (0xFF01, 0xFF60),
+    (0xFFE0, 0xFFE7),
+    (0x1F000, 0x1FAFF),
+    (0x20000, 0x3FFFD),
+];
+
+/// Returns true if a character is drawn upright in vertical text
+/// # Arguments
+/// * `c` - The character
+pub fn is_upright(c: char) -> bool {
+    let c = c as u32;
+
+    // The last range starting at or before the character
+    let i = UPRIGHT.partition_point(|&(start, _)| start <= c);
+    i > 0 && c <= UPRIGHT[i - 1].1
+}
+
+/// Turns glyphs shaped horizontally sideways, so they run down a column of vertical text
+///
+/// The glyphs are rotated 90 degrees clockwise about the pen position, and moved across the
+/// column so the font's ascender to descender is centered on the column's center line.
+/// # Arguments
+/// * `glyphs` - The glyphs of a run shaped left to right or right to left
+/// * `metrics` - Metrics of the font the run was shaped with, at the size it is drawn
+pub fn turn_sideways(glyphs: &mut [ShapedGlyph], metrics: &FontMetrics) {
+    let shift = -(metrics.ascender + metrics.descender) / 2.0;
+
+    for glyph in glyphs.iter_mut() {
+        // Rightwards becomes downwards and upwards becomes rightwards
+        glyph.advance = (glyph.advance.1, -glyph.advance.0);
+        glyph.offset = (glyph.offset.1 + shift, -glyph.offset.0);
+        glyph.sideways = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::FontInstance;
+
+    fn glyph(advance: (f32, f32), offset: (f32, f32)) -> ShapedGlyph {
+        ShapedGlyph {
+            font: FontInstance::default(),
+            glyph_index: 1,
+            cluster: 0,
+            advance,
+            offset,
+            rtl: false,
+            sideways: false,
+        }
+    }
+
+    fn is_upright_code(c: u32) -> bool {
+        is_upright(char::from_u32(c).unwrap())
+    }
+
+    #[test]
+    fn upright_ranges_are_sorted_and_do_not_overlap() {
+        assert!(UPRIGHT.iter().all(|&(start, end)| start <= end));
+        assert!(UPRIGHT.windows(2).all(|pair| pair[0].1 < pair[1].0));
+    }
+
+    #[test]
+    fn latin_is_turned_sideways() {
+        assert!(!is_upright('A'));
+        assert!(!is_upright('1'));
+        assert!(!is_upright(' '));
+    }
+
+    #[test]
+    fn east_asian_characters_are_upright() {
+        assert!(is_upright('あ'));
+        assert!(is_upright('漢'));
+        assert!(is_upright('한'));
+        assert!(is_upright('\u{FF01}'));
+    }
+
+    #[test]
+    fn half_width_forms_are_turned_sideways() {
+        assert!(is_upright('\u{FF60}'));
+        assert!(!is_upright('\u{FF61}'));
+        assert!(!is_upright('ｱ'));
+        assert!(!is_upright('\u{FFA1}'));
+        assert!(is_upright('\u{FFE0}'));
+        assert!(!is_upright('\u{FFE8}'));
+    }
+
+    #[test]
+    fn range_edges_are_included() {
+        for &(start, end) in UPRIGHT.iter() {
+            assert!(is_upright_code(start));
+            assert!(is_upright_code(end));
+        }
+        assert!(!is_upright_code(0x00A6));
+        assert!(!is_upright_code(0x10FF));
+        assert!(!is_upright_code(0x1200));
+        assert!(!is_upright_code(0x3FFFE));
+    }
+
+    #[test]
+    fn turning_sideways_rotates_advances_and_offsets() {
+        let metrics = FontMetrics {
+            ascender: 10.0,
+            descender: -10.0,
+            line_gap: 0.0,
+        };
+        let mut glyphs = [glyph((8.0, 0.0), (1.0, 2.0))];
+
+        turn_sideways(&mut glyphs, &metrics);
+
+        assert_eq!(glyphs[0].advance, (0.0, -8.0));
+        assert_eq!(glyphs[0].offset, (2.0, -1.0));
+        assert_eq!(glyphs[0].line_advance(), 8.0);
+        assert!(glyphs[0].sideways);
+    }
+
+    #[test]
+    fn turning_sideways_centers_the_font_on_the_column() {
+        let metrics = FontMetrics {
+            ascender: 12.0,
+            descender: -4.0,
+            line_gap: 2.0,
+        };
+        let mut glyphs = [glyph((8.0, 0.0), (0.0, 0.0)), glyph((6.0, 0.0), (0.0, 3.0))];
+
+        turn_sideways(&mut glyphs, &metrics);
+
+        // The middle of the ascender to descender is 4 pixels above the baseline
+        assert_eq!(glyphs[0].offset, (-4.0, 0.0));
+        assert_eq!(glyphs[1].offset, (-1.0, 0.0));
+        assert_eq!(glyphs[1].advance, (0.0, -6.0));
+    }
+}
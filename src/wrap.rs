@@ -0,0 +1,244 @@
+use crate::shaping::ShapedGlyph;
+
+use unicode_linebreak::linebreaks;
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::ops::Range;
+
+/// Advance widths of a shaped line indexed by the byte offset of each cluster
+struct ClusterWidths {
+    /// Byte offset of each cluster, in increasing order
+    clusters: Vec<usize>,
+    /// Total advance of all clusters before each entry in `clusters`
+    prefix: Vec<f32>,
+}
+
+impl ClusterWidths {
+    /// Sums the advances of the glyphs in each cluster
+    /// # Arguments
+    /// * `glyphs` - The shaped glyphs of the line, in any order
+    fn new(glyphs: &[ShapedGlyph]) -> ClusterWidths {
+        let mut advances: Vec<(usize, f32)> = glyphs
+            .iter()
+            .map(|glyph| (glyph.cluster, glyph.line_advance()))
+            .collect();
+        advances.sort_by_key(|(cluster, _)| *cluster);
+
+        let mut clusters = vec![];
+        let mut prefix = vec![0.0];
+        for (cluster, advance) in advances {
+            if clusters.last() != Some(&cluster) {
+                clusters.push(cluster);
+                prefix.push(*prefix.last().unwrap());
+            }
+            *prefix.last_mut().unwrap() += advance;
+        }
+
+        ClusterWidths { clusters, prefix }
+    }
+
+    /// Returns the advance of the clusters starting in a byte range
+    /// # Arguments
+    /// * `start` - Byte offset of the start of the range
+    /// * `end` - Byte offset of the end of the range
+    fn width(&self, start: usize, end: usize) -> f32 {
+        let first = self.clusters.partition_point(|&cluster| cluster < start);
+        let last = self.clusters.partition_point(|&cluster| cluster < end);
+
+        if last <= first {
+            0.0
+        } else {
+            self.prefix[last] - self.prefix[first]
+        }
+    }
+}
+
+/// Returns the byte offset of the end of a range with trailing whitespace removed
+/// # Arguments
+/// * `text` - The text containing the range
+/// * `start` - Byte offset of the start of the range
+/// * `end` - Byte offset of the end of the range
+fn trim_end(text: &str, start: usize, end: usize) -> usize {
+    start + text[start..end].trim_end().len()
+}
+
+/// Breaks a single line of text into lines no wider than a maximum width
+///
+/// Lines are broken at the break opportunities defined by the Unicode line breaking algorithm
+/// (UAX #14). Words that are wider than the maximum width on their own are broken between
+/// grapheme clusters. Trailing whitespace does not count towards the width of a line and stays
+/// at the end of the line it follows. Columns of vertical text are wrapped to a maximum height
+/// the same way.
+/// # Arguments
+/// * `text` - The text containing the line
+/// * `range` - Byte range of the line within the text, without any line break
+/// * `glyphs` - The shaped glyphs of the line, with clusters relative to the text
+/// * `max_width` - The maximum width of a line
+pub fn wrap_line(
+    text: &str,
+    range: Range<usize>,
+    glyphs: &[ShapedGlyph],
+    max_width: f32,
+) -> Vec<Range<usize>> {
+    let widths = ClusterWidths::new(glyphs);
+    let fits =
+        |start: usize, end: usize| widths.width(start, trim_end(text, start, end)) <= max_width;
+
+    let mut lines = vec![];
+    let mut line_start = range.start;
+    let mut last_break: Option<usize> = None;
+
+    for (offset, _) in linebreaks(&text[range.clone()]) {
+        let position = range.start + offset;
+
+        if fits(line_start, position) {
+            last_break = Some(position);
+            continue;
+        }
+
+        // End the line at the last opportunity that fit
+        if let Some(end) = last_break {
+            lines.push(line_start..end);
+            line_start = end;
+        }
+
+        // A word too long to fit on a line of its own is broken between grapheme clusters
+        while !fits(line_start, position) {
+            let mut end = line_start;
+            for (i, grapheme) in text[line_start..position].grapheme_indices(true) {
+                let next = line_start + i + grapheme.len();
+
+                // Always place at least one grapheme on a line, whitespace never starts one
+                let whitespace = grapheme.chars().all(char::is_whitespace);
+                if end > line_start && !whitespace && !fits(line_start, next) {
+                    break;
+                }
+                end = next;
+            }
+
+            if end >= position {
+                break;
+            }
+
+            lines.push(line_start..end);
+            line_start = end;
+        }
+
+        last_break = Some(position);
+    }
+
+    // The remaining text forms the last line, which may be empty
+    if line_start < range.end || lines.is_empty() {
+        lines.push(line_start..range.end);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates one glyph per grapheme cluster of a line, each advancing the pen by `advance`
+    /// # Arguments
+    /// * `text` - The text containing the line
+    /// * `range` - Byte range of the line
+    /// * `advance` - Advance of each glyph, horizontal or vertical
+    fn glyphs(text: &str, range: Range<usize>, advance: (f32, f32)) -> Vec<ShapedGlyph> {
+        text[range.clone()]
+            .grapheme_indices(true)
+            .map(|(i, _)| ShapedGlyph {
+                font: Default::default(),
+                glyph_index: 0,
+                cluster: range.start + i,
+                advance,
+                offset: (0.0, 0.0),
+                rtl: false,
+                sideways: false,
+            })
+            .collect()
+    }
+
+    /// Wraps a whole text whose graphemes are all 10 pixels wide
+    fn wrap(text: &str, max_width: f32) -> Vec<Range<usize>> {
+        let glyphs = glyphs(text, 0..text.len(), (10.0, 0.0));
+        wrap_line(text, 0..text.len(), &glyphs, max_width)
+    }
+
+    #[test]
+    fn short_line_is_not_wrapped() {
+        assert_eq!(wrap("hello world", 200.0), vec![0..11]);
+    }
+
+    #[test]
+    fn empty_line_is_kept() {
+        assert_eq!(wrap("", 50.0), vec![0..0]);
+    }
+
+    #[test]
+    fn breaks_after_spaces() {
+        assert_eq!(wrap("hello world", 60.0), vec![0..6, 6..11]);
+        assert_eq!(wrap("aa bb cc dd", 50.0), vec![0..6, 6..11]);
+    }
+
+    #[test]
+    fn breaks_after_hyphens() {
+        assert_eq!(wrap("well-known", 60.0), vec![0..5, 5..10]);
+    }
+
+    #[test]
+    fn trailing_whitespace_does_not_count() {
+        // "abcd  " is 60 pixels wide, but only 40 without its spaces
+        assert_eq!(wrap("abcd  efgh", 40.0), vec![0..6, 6..10]);
+        assert_eq!(wrap("     ", 20.0), vec![0..5]);
+    }
+
+    #[test]
+    fn long_words_are_split_between_graphemes() {
+        assert_eq!(wrap("abcdefghij", 35.0), vec![0..3, 3..6, 6..9, 9..10]);
+        assert_eq!(wrap("ab abcdefgh", 40.0), vec![0..3, 3..7, 7..11]);
+    }
+
+    #[test]
+    fn graphemes_are_never_split() {
+        // Three accented letters of three bytes each
+        let text = "e\u{301}e\u{301}e\u{301}";
+        assert_eq!(wrap(text, 15.0), vec![0..3, 3..6, 6..9]);
+    }
+
+    #[test]
+    fn every_line_has_a_grapheme() {
+        // Lines narrower than a single grapheme still make progress
+        assert_eq!(wrap("abc", 5.0), vec![0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn ranges_are_relative_to_the_text() {
+        let text = "first\nhello world";
+        let glyphs = glyphs(text, 6..17, (10.0, 0.0));
+
+        assert_eq!(wrap_line(text, 6..17, &glyphs, 60.0), vec![6..12, 12..17]);
+    }
+
+    #[test]
+    fn ligatures_count_once() {
+        // "ffi" shaped into a single 10 pixel glyph makes the line 40 pixels wide rather than 60
+        let text = "ffi ab";
+        let mut glyphs = glyphs(text, 0..text.len(), (10.0, 0.0));
+        glyphs.drain(1..3);
+
+        assert_eq!(wrap_line(text, 0..text.len(), &glyphs, 40.0), vec![0..6]);
+    }
+
+    #[test]
+    fn columns_wrap_by_height() {
+        // Vertical glyphs advance downwards
+        let text = "hello world";
+        let glyphs = glyphs(text, 0..text.len(), (0.0, -10.0));
+
+        assert_eq!(
+            wrap_line(text, 0..text.len(), &glyphs, 60.0),
+            vec![0..6, 6..11]
+        );
+    }
+}